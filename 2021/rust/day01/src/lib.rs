@@ -1,3 +1,7 @@
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufRead};
 use std::str::FromStr;
 
 fn parse_values<T: FromStr>(input: &str) -> Vec<T> {
@@ -7,18 +11,198 @@ fn parse_values<T: FromStr>(input: &str) -> Vec<T> {
         .collect()
 }
 
+// Two overlapping windows share all but their first and last elements, so
+// comparing their sums reduces to comparing those two elements.
+pub fn count_increases<T: PartialOrd>(values: &[T], window: usize) -> usize {
+    if window == 0 {
+        return 0;
+    }
+    values
+        .windows(window + 1)
+        .filter(|x| x[window] > x[0])
+        .count()
+}
+
+pub fn count_increases_reader<T, R>(reader: R, window: usize) -> io::Result<usize>
+where
+    T: FromStr + PartialOrd,
+    R: BufRead,
+{
+    if window == 0 {
+        return Ok(0);
+    }
+
+    let mut last = VecDeque::with_capacity(window + 1);
+    let mut count = 0;
+    for line in reader.lines() {
+        let line = line?;
+        for value in line
+            .split_ascii_whitespace()
+            .filter_map(|x| x.parse::<T>().ok())
+        {
+            last.push_back(value);
+            if last.len() > window {
+                if last[window] > last[0] {
+                    count += 1;
+                }
+                last.pop_front();
+            }
+        }
+    }
+    Ok(count)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Run {
+    pub start: usize,
+    pub len: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Jump {
+    pub index: usize,
+    pub amount: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowSummary {
+    pub window: usize,
+    pub increases: usize,
+    pub decreases: usize,
+    pub unchanged: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SonarReport {
+    pub readings: usize,
+    pub longest_increasing_run: Run,
+    pub plateaus: usize,
+    pub largest_rise: Option<Jump>,
+    pub largest_fall: Option<Jump>,
+    pub windows: Vec<WindowSummary>,
+}
+
+// Jump indices point at the reading after the change.
+pub fn sonar_report(values: &[u32], windows: &[usize]) -> SonarReport {
+    let mut longest = Run {
+        start: 0,
+        len: values.len().min(1),
+    };
+    let mut current = longest;
+    let mut plateaus = 0;
+    let mut largest_rise: Option<Jump> = None;
+    let mut largest_fall: Option<Jump> = None;
+
+    for (i, pair) in values.windows(2).enumerate() {
+        let index = i + 1;
+        match pair[1].cmp(&pair[0]) {
+            Ordering::Greater => {
+                current.len += 1;
+                let amount = pair[1] - pair[0];
+                if largest_rise.is_none_or(|x| amount > x.amount) {
+                    largest_rise = Some(Jump { index, amount });
+                }
+            }
+            Ordering::Less => {
+                current = Run {
+                    start: index,
+                    len: 1,
+                };
+                let amount = pair[0] - pair[1];
+                if largest_fall.is_none_or(|x| amount > x.amount) {
+                    largest_fall = Some(Jump { index, amount });
+                }
+            }
+            Ordering::Equal => {
+                current = Run {
+                    start: index,
+                    len: 1,
+                };
+                plateaus += 1;
+            }
+        }
+        if current.len > longest.len {
+            longest = current;
+        }
+    }
+
+    let windows = windows
+        .iter()
+        .map(|&window| summarize_window(values, window))
+        .collect();
+
+    SonarReport {
+        readings: values.len(),
+        longest_increasing_run: longest,
+        plateaus,
+        largest_rise,
+        largest_fall,
+        windows,
+    }
+}
+
+fn summarize_window(values: &[u32], window: usize) -> WindowSummary {
+    let mut summary = WindowSummary {
+        window,
+        increases: 0,
+        decreases: 0,
+        unchanged: 0,
+    };
+    if window == 0 {
+        return summary;
+    }
+    for x in values.windows(window + 1) {
+        match x[window].cmp(&x[0]) {
+            Ordering::Greater => summary.increases += 1,
+            Ordering::Less => summary.decreases += 1,
+            Ordering::Equal => summary.unchanged += 1,
+        }
+    }
+    summary
+}
+
+impl fmt::Display for SonarReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "readings: {}", self.readings)?;
+        let run = self.longest_increasing_run;
+        writeln!(
+            f,
+            "longest increasing run: {} readings starting at {}",
+            run.len, run.start
+        )?;
+        writeln!(f, "plateaus: {}", self.plateaus)?;
+        match self.largest_rise {
+            Some(x) => writeln!(f, "largest rise: {} at {}", x.amount, x.index)?,
+            None => writeln!(f, "largest rise: none")?,
+        }
+        match self.largest_fall {
+            Some(x) => writeln!(f, "largest fall: {} at {}", x.amount, x.index)?,
+            None => writeln!(f, "largest fall: none")?,
+        }
+        for x in self.windows.iter() {
+            writeln!(
+                f,
+                "window {}: {} increases, {} decreases, {} unchanged",
+                x.window, x.increases, x.decreases, x.unchanged
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub fn report(input: &str) -> SonarReport {
+    let values: Vec<u32> = parse_values(input);
+    sonar_report(&values, &[1, 3])
+}
+
 pub fn solve1(input: &str) -> usize {
     let values: Vec<u16> = parse_values(input);
-    values.windows(2).filter(|x| x[1] > x[0]).count()
+    count_increases(&values, 1)
 }
 
 pub fn solve2(input: &str) -> usize {
     let values: Vec<u32> = parse_values(input);
-    values
-        .windows(3)
-        .zip(values.windows(3).skip(1))
-        .filter(|(first, second)| second.iter().sum::<u32>() > first.iter().sum())
-        .count()
+    count_increases(&values, 3)
 }
 
 #[cfg(test)]
@@ -36,4 +220,52 @@ mod tests {
         let input = include_str!("../test");
         assert_eq!(solve2(input), 5);
     }
+
+    #[test]
+    fn test_report() {
+        let input = include_str!("../test");
+        let report = report(input);
+        assert_eq!(report.readings, 10);
+        assert_eq!(report.longest_increasing_run, Run { start: 0, len: 4 });
+        assert_eq!(report.plateaus, 0);
+        assert_eq!(
+            report.largest_rise,
+            Some(Jump {
+                index: 6,
+                amount: 33
+            })
+        );
+        assert_eq!(
+            report.largest_fall,
+            Some(Jump {
+                index: 4,
+                amount: 10
+            })
+        );
+        assert_eq!(report.windows[0].increases, solve1(input));
+        assert_eq!(report.windows[1].increases, solve2(input));
+        assert_eq!(report.windows[1].unchanged, 1);
+    }
+
+    #[test]
+    fn test_count_increases() {
+        let input = include_str!("../test");
+        let values: Vec<u32> = parse_values(input);
+        for window in 0..=values.len() + 1 {
+            let expected = if window == 0 {
+                0
+            } else {
+                values
+                    .windows(window)
+                    .zip(values.windows(window).skip(1))
+                    .filter(|(first, second)| second.iter().sum::<u32>() > first.iter().sum())
+                    .count()
+            };
+            assert_eq!(count_increases(&values, window), expected);
+            assert_eq!(
+                count_increases_reader::<u32, _>(input.as_bytes(), window).unwrap(),
+                expected
+            );
+        }
+    }
 }
@@ -2,4 +2,5 @@ fn main() {
     let input = include_str!("../input");
     println!("{}", day01::solve1(input));
     println!("{}", day01::solve2(input));
+    print!("{}", day01::report(input));
 }
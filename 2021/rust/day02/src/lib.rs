@@ -1,55 +1,502 @@
-enum Direction {
-    Forward(usize),
-    Down(usize),
-    Up(usize),
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::slice;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward(i64),
+    Back(i64),
+    Down(i64),
+    Up(i64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Move(Direction),
+    Repeat(usize, Vec<Command>),
+    Call(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Program {
+    pub commands: Vec<Command>,
+    pub macros: HashMap<String, Vec<Command>>,
 }
 
-fn parse_direction(s: &str) -> Option<Direction> {
-    let tokens: Vec<_> = s.split_ascii_whitespace().collect();
+impl Program {
+    pub fn run(&self, submarine: &mut Submarine) -> Result<Position, OverflowError> {
+        self.run_commands(&self.commands, submarine)?;
+        Ok(submarine.position())
+    }
 
-    let value = tokens[1].parse().ok()?;
+    fn run_commands(
+        &self,
+        commands: &[Command],
+        submarine: &mut Submarine,
+    ) -> Result<(), OverflowError> {
+        for command in commands.iter() {
+            match command {
+                Command::Move(d) => {
+                    submarine.apply(*d)?;
+                }
+                Command::Repeat(n, body) => {
+                    for _ in 0..*n {
+                        self.run_commands(body, submarine)?;
+                    }
+                }
+                Command::Call(name) => self.run_commands(&self.macros[name], submarine)?,
+            }
+        }
+        Ok(())
+    }
 
-    match tokens[0] {
-        "forward" => Some(Direction::Forward(value)),
-        "down" => Some(Direction::Down(value)),
-        "up" => Some(Direction::Up(value)),
-        _ => None,
+    pub fn expand(&self) -> Vec<Direction> {
+        let mut directions = Vec::new();
+        self.expand_commands(&self.commands, &mut directions);
+        directions
+    }
+
+    fn expand_commands(&self, commands: &[Command], directions: &mut Vec<Direction>) {
+        for command in commands.iter() {
+            match command {
+                Command::Move(d) => directions.push(*d),
+                Command::Repeat(n, body) => {
+                    for _ in 0..*n {
+                        self.expand_commands(body, directions);
+                    }
+                }
+                Command::Call(name) => self.expand_commands(&self.macros[name], directions),
+            }
+        }
     }
 }
 
-fn parse_values(input: &str) -> Vec<Direction> {
-    input.lines().filter_map(parse_direction).collect()
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnknownCommand(String),
+    InvalidValue(String),
+    MissingValue,
+    UnexpectedToken(String),
+    UnknownMacro(String),
+    DuplicateMacro(String),
+    UnexpectedClose,
+    UnclosedBlock,
 }
 
-pub fn solve1(input: &str) -> usize {
-    let values = parse_values(input);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub kind: ParseErrorKind,
+}
 
-    let (mut horizontal, mut depth) = (0, 0);
-    for d in values.into_iter() {
-        match d {
-            Direction::Forward(x) => horizontal += x,
-            Direction::Down(x) => depth += x,
-            Direction::Up(x) => depth -= x,
-        };
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            ParseErrorKind::UnknownCommand(x) => write!(f, "unknown command `{x}`"),
+            ParseErrorKind::InvalidValue(x) => write!(f, "invalid value `{x}`"),
+            ParseErrorKind::MissingValue => write!(f, "missing value"),
+            ParseErrorKind::UnexpectedToken(x) => write!(f, "unexpected `{x}`"),
+            ParseErrorKind::UnknownMacro(x) => write!(f, "macro `{x}` is not defined"),
+            ParseErrorKind::DuplicateMacro(x) => write!(f, "macro `{x}` is already defined"),
+            ParseErrorKind::UnexpectedClose => write!(f, "`}}` without an open block"),
+            ParseErrorKind::UnclosedBlock => write!(f, "block is never closed"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    Simple,
+    Aimed,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Position {
+    pub horizontal: i64,
+    pub depth: i64,
+    pub aim: i64,
+}
+
+impl Position {
+    pub fn product(&self) -> Option<i64> {
+        self.horizontal.checked_mul(self.depth)
     }
-    horizontal * depth
 }
 
-pub fn solve2(input: &str) -> usize {
-    let values = parse_values(input);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverflowError {
+    pub step: usize,
+    pub position: Position,
+    pub direction: Direction,
+}
+
+impl fmt::Display for OverflowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "step {}: applying {:?} to {:?} overflows",
+            self.step, self.direction, self.position
+        )
+    }
+}
+
+impl std::error::Error for OverflowError {}
+
+#[derive(Debug, Clone)]
+pub struct Submarine {
+    model: Model,
+    position: Position,
+    steps: usize,
+}
+
+impl Submarine {
+    pub fn new(model: Model) -> Submarine {
+        Submarine {
+            model,
+            position: Position::default(),
+            steps: 0,
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    // On overflow the submarine keeps its previous position.
+    pub fn apply(&mut self, direction: Direction) -> Result<Position, OverflowError> {
+        let next = self.next_position(direction).ok_or(OverflowError {
+            step: self.steps,
+            position: self.position,
+            direction,
+        })?;
+        self.position = next;
+        self.steps += 1;
+        Ok(next)
+    }
 
-    let (mut horizontal, mut depth, mut aim) = (0, 0, 0);
-    for d in values.into_iter() {
-        match d {
-            Direction::Forward(x) => {
-                horizontal += x;
-                depth += aim * x;
+    fn next_position(&self, direction: Direction) -> Option<Position> {
+        let mut p = self.position;
+        match (self.model, direction) {
+            (Model::Simple, Direction::Forward(x)) => {
+                p.horizontal = p.horizontal.checked_add(x)?;
+            }
+            (Model::Simple, Direction::Back(x)) => {
+                p.horizontal = p.horizontal.checked_sub(x)?;
+            }
+            (Model::Simple, Direction::Down(x)) => p.depth = p.depth.checked_add(x)?,
+            (Model::Simple, Direction::Up(x)) => p.depth = p.depth.checked_sub(x)?,
+            (Model::Aimed, Direction::Forward(x)) => {
+                p.horizontal = p.horizontal.checked_add(x)?;
+                p.depth = p.depth.checked_add(p.aim.checked_mul(x)?)?;
             }
-            Direction::Down(x) => aim += x,
-            Direction::Up(x) => aim -= x,
+            (Model::Aimed, Direction::Back(x)) => {
+                p.horizontal = p.horizontal.checked_sub(x)?;
+                p.depth = p.depth.checked_sub(p.aim.checked_mul(x)?)?;
+            }
+            (Model::Aimed, Direction::Down(x)) => p.aim = p.aim.checked_add(x)?,
+            (Model::Aimed, Direction::Up(x)) => p.aim = p.aim.checked_sub(x)?,
+        }
+        Some(p)
+    }
+}
+
+pub struct Trajectory<'a> {
+    submarine: Submarine,
+    directions: slice::Iter<'a, Direction>,
+    failed: bool,
+}
+
+impl Iterator for Trajectory<'_> {
+    type Item = Result<Position, OverflowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = self.submarine.apply(*self.directions.next()?);
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+pub fn trajectory(model: Model, directions: &[Direction]) -> Trajectory<'_> {
+    Trajectory {
+        submarine: Submarine::new(model),
+        directions: directions.iter(),
+        failed: false,
+    }
+}
+
+enum Block {
+    Root,
+    Repeat(usize),
+    Macro(String),
+}
+
+struct Frame {
+    block: Block,
+    line: usize,
+    commands: Vec<Command>,
+}
+
+fn parse_number<T: std::str::FromStr>(token: Option<&str>) -> Result<T, ParseErrorKind> {
+    let token = token.ok_or(ParseErrorKind::MissingValue)?;
+    token
+        .parse()
+        .map_err(|_| ParseErrorKind::InvalidValue(token.to_string()))
+}
+
+// Movement amounts are magnitudes; `back` and `up` move the other way.
+fn parse_magnitude(token: Option<&str>) -> Result<i64, ParseErrorKind> {
+    let value: i64 = parse_number(token)?;
+    if value < 0 {
+        return Err(ParseErrorKind::InvalidValue(token.unwrap().to_string()));
+    }
+    Ok(value)
+}
+
+fn expect_end<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<(), ParseErrorKind> {
+    match tokens.next() {
+        Some(x) => Err(ParseErrorKind::UnexpectedToken(x.to_string())),
+        None => Ok(()),
+    }
+}
+
+fn expect_open<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<(), ParseErrorKind> {
+    match tokens.next() {
+        Some("{") => expect_end(tokens),
+        Some(x) => Err(ParseErrorKind::UnexpectedToken(x.to_string())),
+        None => Err(ParseErrorKind::UnexpectedToken("end of line".to_string())),
+    }
+}
+
+// Macros must be defined before they are called, which also rules out recursion.
+pub fn parse_program(input: &str) -> Result<Program, ParseError> {
+    let mut macros: HashMap<String, Vec<Command>> = HashMap::new();
+    let mut stack = vec![Frame {
+        block: Block::Root,
+        line: 0,
+        commands: Vec::new(),
+    }];
+
+    for (i, line) in input.lines().enumerate() {
+        let line_number = i + 1;
+        let error = |kind| ParseError {
+            line: line_number,
+            kind,
+        };
+
+        let code = line.split('#').next().unwrap();
+        let mut tokens = code.split_ascii_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        let command = match keyword {
+            "forward" | "back" | "down" | "up" => {
+                let value = parse_magnitude(tokens.next()).map_err(error)?;
+                expect_end(tokens).map_err(error)?;
+                let direction = match keyword {
+                    "forward" => Direction::Forward(value),
+                    "back" => Direction::Back(value),
+                    "down" => Direction::Down(value),
+                    _ => Direction::Up(value),
+                };
+                Command::Move(direction)
+            }
+            "repeat" => {
+                let count = parse_number(tokens.next()).map_err(error)?;
+                expect_open(tokens).map_err(error)?;
+                stack.push(Frame {
+                    block: Block::Repeat(count),
+                    line: line_number,
+                    commands: Vec::new(),
+                });
+                continue;
+            }
+            "macro" => {
+                let name = tokens.next().ok_or(error(ParseErrorKind::MissingValue))?;
+                if !is_identifier(name) {
+                    return Err(error(ParseErrorKind::InvalidValue(name.to_string())));
+                }
+                expect_open(tokens).map_err(error)?;
+                stack.push(Frame {
+                    block: Block::Macro(name.to_string()),
+                    line: line_number,
+                    commands: Vec::new(),
+                });
+                continue;
+            }
+            "}" => {
+                expect_end(tokens).map_err(error)?;
+                if stack.len() == 1 {
+                    return Err(error(ParseErrorKind::UnexpectedClose));
+                }
+                let frame = stack.pop().unwrap();
+                match frame.block {
+                    Block::Repeat(count) => Command::Repeat(count, frame.commands),
+                    Block::Macro(name) => {
+                        if macros.contains_key(&name) {
+                            return Err(error(ParseErrorKind::DuplicateMacro(name)));
+                        }
+                        macros.insert(name, frame.commands);
+                        continue;
+                    }
+                    Block::Root => unreachable!(),
+                }
+            }
+            name if is_identifier(name) => {
+                if tokens.next().is_some() {
+                    return Err(error(ParseErrorKind::UnknownCommand(name.to_string())));
+                }
+                if !macros.contains_key(name) {
+                    return Err(error(ParseErrorKind::UnknownMacro(name.to_string())));
+                }
+                Command::Call(name.to_string())
+            }
+            x => return Err(error(ParseErrorKind::UnknownCommand(x.to_string()))),
         };
+        stack.last_mut().unwrap().commands.push(command);
+    }
+
+    if stack.len() > 1 {
+        return Err(ParseError {
+            line: stack.last().unwrap().line,
+            kind: ParseErrorKind::UnclosedBlock,
+        });
     }
-    horizontal * depth
+
+    Ok(Program {
+        commands: stack.pop().unwrap().commands,
+        macros,
+    })
+}
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes().all(|x| x.is_ascii_alphanumeric() || x == b'_')
+        && !s.as_bytes()[0].is_ascii_digit()
+}
+
+fn path(model: Model, directions: &[Direction]) -> Result<Vec<Position>, OverflowError> {
+    let mut path = vec![Position::default()];
+    for position in trajectory(model, directions) {
+        path.push(position?);
+    }
+    Ok(path)
+}
+
+pub fn trajectory_csv(directions: &[Direction]) -> Result<String, OverflowError> {
+    let simple = path(Model::Simple, directions)?;
+    let aimed = path(Model::Aimed, directions)?;
+
+    let mut csv = String::from("step,horizontal1,depth1,horizontal2,depth2,aim2\n");
+    for (step, (p1, p2)) in simple.iter().zip(aimed.iter()).enumerate() {
+        writeln!(
+            csv,
+            "{},{},{},{},{},{}",
+            step, p1.horizontal, p1.depth, p2.horizontal, p2.depth, p2.aim
+        )
+        .unwrap();
+    }
+    Ok(csv)
+}
+
+const SVG_WIDTH: f64 = 800.0;
+const SVG_HEIGHT: f64 = 400.0;
+const SVG_MARGIN: f64 = 40.0;
+
+fn bounds(values: impl Iterator<Item = i64>) -> (f64, f64) {
+    let (min, max) = values.fold((0, 0), |(min, max), x| (min.min(x), max.max(x)));
+    (min as f64, (max as f64).max(min as f64 + 1.0))
+}
+
+// Both models share the horizontal axis, but part 2 dives orders of magnitude
+// deeper than part 1, so each profile gets its own vertical scale.
+pub fn trajectory_svg(directions: &[Direction]) -> Result<String, OverflowError> {
+    let profiles = [
+        ("part 1", "steelblue", path(Model::Simple, directions)?),
+        ("part 2", "firebrick", path(Model::Aimed, directions)?),
+    ];
+
+    let (x_min, x_max) = bounds(
+        profiles
+            .iter()
+            .flat_map(|(_, _, path)| path.iter().map(|p| p.horizontal)),
+    );
+    let plot_width = SVG_WIDTH - 2.0 * SVG_MARGIN;
+    let plot_height = SVG_HEIGHT - 2.0 * SVG_MARGIN;
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{SVG_WIDTH}" height="{SVG_HEIGHT}" viewBox="0 0 {SVG_WIDTH} {SVG_HEIGHT}">"#
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<rect x="{SVG_MARGIN}" y="{SVG_MARGIN}" width="{plot_width}" height="{plot_height}" fill="none" stroke="gray"/>"#
+    )
+    .unwrap();
+
+    for (i, (name, colour, path)) in profiles.iter().enumerate() {
+        let (y_min, y_max) = bounds(path.iter().map(|p| p.depth));
+        let points: Vec<String> = path
+            .iter()
+            .map(|p| {
+                let x = SVG_MARGIN + (p.horizontal as f64 - x_min) / (x_max - x_min) * plot_width;
+                let y = SVG_MARGIN + (p.depth as f64 - y_min) / (y_max - y_min) * plot_height;
+                format!("{x:.2},{y:.2}")
+            })
+            .collect();
+        writeln!(
+            svg,
+            r#"<polyline fill="none" stroke="{colour}" stroke-width="1.5" points="{}"/>"#,
+            points.join(" ")
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" font-family="monospace" font-size="12" fill="{colour}">{name}: depth {y_min}..{y_max}</text>"#,
+            SVG_MARGIN,
+            SVG_MARGIN - 8.0 - 14.0 * (profiles.len() - 1 - i) as f64,
+        )
+        .unwrap();
+    }
+    writeln!(
+        svg,
+        r#"<text x="{}" y="{}" font-family="monospace" font-size="12">horizontal {x_min}..{x_max}</text>"#,
+        SVG_MARGIN,
+        SVG_HEIGHT - SVG_MARGIN / 2.0,
+    )
+    .unwrap();
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+pub fn solve1(input: &str) -> i64 {
+    let program = parse_program(input).unwrap();
+    program
+        .run(&mut Submarine::new(Model::Simple))
+        .unwrap()
+        .product()
+        .unwrap()
+}
+
+pub fn solve2(input: &str) -> i64 {
+    let program = parse_program(input).unwrap();
+    program
+        .run(&mut Submarine::new(Model::Aimed))
+        .unwrap()
+        .product()
+        .unwrap()
 }
 
 #[cfg(test)]
@@ -67,4 +514,101 @@ mod tests {
         let input = include_str!("../test");
         assert_eq!(solve2(input), 900);
     }
+
+    #[test]
+    fn test_trajectory() {
+        let values = parse_program("forward 2\nup 3\ndown 1\nforward 4")
+            .unwrap()
+            .expand();
+        let steps: Vec<_> = trajectory(Model::Aimed, &values)
+            .map(|x| x.unwrap())
+            .map(|p| (p.horizontal, p.depth, p.aim))
+            .collect();
+        assert_eq!(steps, [(2, 0, 0), (2, 0, -3), (2, 0, -2), (6, -8, -2)]);
+        assert_eq!(solve1("up 3\nforward 2"), -6);
+    }
+
+    #[test]
+    fn test_program() {
+        let input = "# mission\nmacro dive {\n  down 2 # steeper\n  forward 1\n}\n\nrepeat 3 {\n  dive\n}\nback 1\n";
+        let program = parse_program(input).unwrap();
+        assert_eq!(program.expand().len(), 7);
+        assert_eq!(solve1(input), 2 * 6);
+        assert_eq!(solve2(input), 2 * (2 + 4 + 6 - 6));
+
+        let error = |input| parse_program(input).unwrap_err();
+        assert_eq!(
+            error("forward 1\nsideways 2"),
+            ParseError {
+                line: 2,
+                kind: ParseErrorKind::UnknownCommand("sideways".to_string())
+            }
+        );
+        assert_eq!(
+            error("up x").kind,
+            ParseErrorKind::InvalidValue("x".to_string())
+        );
+        assert_eq!(
+            error("forward -5").kind,
+            ParseErrorKind::InvalidValue("-5".to_string())
+        );
+        assert_eq!(error("up").kind, ParseErrorKind::MissingValue);
+        assert_eq!(
+            error("up 1 2").kind,
+            ParseErrorKind::UnexpectedToken("2".to_string())
+        );
+        assert_eq!(
+            error("dive").kind,
+            ParseErrorKind::UnknownMacro("dive".to_string())
+        );
+        assert_eq!(error("macro m {\nm\n}").line, 2);
+        assert_eq!(error("up 1\n}").kind, ParseErrorKind::UnexpectedClose);
+        assert_eq!(
+            error("repeat 2 {\nup 1"),
+            ParseError {
+                line: 1,
+                kind: ParseErrorKind::UnclosedBlock
+            }
+        );
+    }
+
+    #[test]
+    fn test_export() {
+        let input = include_str!("../test");
+        let directions = parse_program(input).unwrap().expand();
+
+        let csv = trajectory_csv(&directions).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), directions.len() + 2);
+        assert_eq!(lines[1], "0,0,0,0,0,0");
+        assert_eq!(lines[7], "6,15,10,15,60,10");
+
+        let svg = trajectory_svg(&directions).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<polyline").count(), 2);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let values = [
+            Direction::Down(1),
+            Direction::Forward(i64::MAX),
+            Direction::Forward(2),
+            Direction::Down(1),
+        ];
+        let steps: Vec<_> = trajectory(Model::Aimed, &values).collect();
+        assert_eq!(steps.len(), 3);
+        assert_eq!(
+            steps[2],
+            Err(OverflowError {
+                step: 2,
+                position: Position {
+                    horizontal: i64::MAX,
+                    depth: i64::MAX,
+                    aim: 1
+                },
+                direction: Direction::Forward(2),
+            })
+        );
+    }
 }
@@ -1,5 +1,21 @@
+use std::env;
+use std::fs;
+
 fn main() {
     let input = include_str!("../input");
     println!("{}", day02::solve1(input));
     println!("{}", day02::solve2(input));
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let path = args.next().expect("missing output path");
+        let directions = day02::parse_program(input).unwrap().expand();
+        let contents = match arg.as_str() {
+            "--csv" => day02::trajectory_csv(&directions),
+            "--svg" => day02::trajectory_svg(&directions),
+            _ => panic!("unknown option `{arg}`, expected --csv or --svg"),
+        }
+        .unwrap();
+        fs::write(&path, contents).unwrap();
+    }
 }
@@ -1,9 +1,89 @@
-fn parse_values(input: &str) -> Vec<&str> {
-    input.lines().collect()
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReportError {
+    Empty,
+    InvalidCharacter {
+        line: usize,
+        column: usize,
+        found: char,
+    },
+    WidthMismatch {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
 }
 
-pub fn solve1(input: &str) -> usize {
-    let values = parse_values(input);
+impl fmt::Display for ReportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportError::Empty => write!(f, "report is empty"),
+            ReportError::InvalidCharacter {
+                line,
+                column,
+                found,
+            } => write!(
+                f,
+                "line {line}, column {column}: expected `0` or `1`, found `{found}`"
+            ),
+            ReportError::WidthMismatch {
+                line,
+                expected,
+                found,
+            } => write!(f, "line {line}: expected {expected} bits, found {found}"),
+        }
+    }
+}
+
+impl std::error::Error for ReportError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PowerRates {
+    pub gamma: String,
+    pub epsilon: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LifeSupportRatings {
+    pub oxygen: String,
+    pub co2: String,
+}
+
+fn parse_values(input: &str) -> Result<Vec<&str>, ReportError> {
+    let values: Vec<&str> = input.lines().collect();
+    let width = values.first().ok_or(ReportError::Empty)?.len();
+
+    for (i, number) in values.iter().enumerate() {
+        if let Some((column, found)) = number
+            .chars()
+            .enumerate()
+            .find(|(_, x)| !matches!(x, '0' | '1'))
+        {
+            return Err(ReportError::InvalidCharacter {
+                line: i + 1,
+                column: column + 1,
+                found,
+            });
+        }
+        if number.len() != width {
+            return Err(ReportError::WidthMismatch {
+                line: i + 1,
+                expected: width,
+                found: number.len(),
+            });
+        }
+    }
+
+    // Only blank lines, all of the same zero width.
+    if width == 0 {
+        return Err(ReportError::Empty);
+    }
+    Ok(values)
+}
+
+pub fn power_rates(input: &str) -> Result<PowerRates, ReportError> {
+    let values = parse_values(input)?;
     let numbers_count = values.len();
     let number_length = values[0].len();
 
@@ -16,71 +96,100 @@ pub fn solve1(input: &str) -> usize {
         }
     }
 
-    let gamma = ones_counts
+    let (gamma, epsilon) = ones_counts
         .into_iter()
-        .rev()
-        .enumerate()
-        .filter_map(|(pos, count)| {
+        .map(|count| {
             if ones_more_equal(numbers_count, count) {
-                Some(pos)
+                ('1', '0')
             } else {
-                None
+                ('0', '1')
             }
         })
-        .fold(0, |acc, pos| acc + 2usize.pow(pos as u32));
+        .unzip();
+    Ok(PowerRates { gamma, epsilon })
+}
 
-    let epsilon =
-        !gamma << (usize::BITS as usize - number_length) >> (usize::BITS as usize - number_length);
-    gamma * epsilon
+// Numbers sorted once; every prefix then corresponds to a contiguous range
+// that splits into its `0` and `1` branches at a single partition point.
+pub struct Report<'a> {
+    numbers: Vec<&'a str>,
+    width: usize,
 }
 
-pub fn solve2(input: &str) -> usize {
-    let values = parse_values(input);
+impl<'a> Report<'a> {
+    pub fn new(input: &'a str) -> Result<Report<'a>, ReportError> {
+        let mut numbers = parse_values(input)?;
+        numbers.sort_unstable();
+        let width = numbers[0].len();
+        Ok(Report { numbers, width })
+    }
 
-    let o2 = sieve(values.clone(), ones_more_equal);
-    let o2 = usize::from_str_radix(o2, 2).unwrap();
+    // `keep_ones` receives the size of the remaining range and its count of
+    // ones at the current position. An empty branch is never selected.
+    pub fn rating(&self, keep_ones: impl Fn(usize, usize) -> bool) -> &'a str {
+        let mut range = &self.numbers[..];
+        for i in 0..self.width {
+            if range.len() == 1 {
+                break;
+            }
 
-    let co2 = sieve(values, zeroes_more);
-    let co2 = usize::from_str_radix(co2, 2).unwrap();
+            let split = range.partition_point(|x| x.as_bytes()[i] == b'0');
+            let (zeroes, ones) = range.split_at(split);
+            range = if zeroes.is_empty() || (!ones.is_empty() && keep_ones(range.len(), ones.len()))
+            {
+                ones
+            } else {
+                zeroes
+            };
+        }
+        range[0]
+    }
 
-    o2 * co2
+    pub fn oxygen(&self) -> &'a str {
+        self.rating(ones_more_equal)
+    }
+
+    pub fn co2(&self) -> &'a str {
+        self.rating(zeroes_more)
+    }
 }
 
-fn sieve(mut numbers: Vec<&str>, match_ones: impl Fn(usize, usize) -> bool) -> &str {
-    let number_length = numbers[0].len();
-    for i in 0..number_length {
-        if numbers.len() == 1 {
-            break;
-        }
+pub fn life_support_ratings(input: &str) -> Result<LifeSupportRatings, ReportError> {
+    let report = Report::new(input)?;
+    Ok(LifeSupportRatings {
+        oxygen: report.oxygen().to_string(),
+        co2: report.co2().to_string(),
+    })
+}
 
-        let ones_count = numbers
-            .iter()
-            .filter(|x| *x.as_bytes().get(i).unwrap() == b'1')
-            .count();
-
-        let bit = if match_ones(numbers.len(), ones_count) {
-            b'1'
-        } else {
-            b'0'
-        };
-
-        numbers = numbers
-            .into_iter()
-            .filter(|x| *x.as_bytes().get(i).unwrap() == bit)
-            .collect();
-    }
-    numbers[0]
+pub fn solve1(input: &str) -> usize {
+    let rates = power_rates(input).unwrap();
+    let gamma = usize::from_str_radix(&rates.gamma, 2).unwrap();
+    let epsilon = usize::from_str_radix(&rates.epsilon, 2).unwrap();
+    gamma * epsilon
 }
 
-fn ones_more_equal(all: usize, ones: usize) -> bool {
+pub fn solve2(input: &str) -> usize {
+    let ratings = life_support_ratings(input).unwrap();
+    let o2 = usize::from_str_radix(&ratings.oxygen, 2).unwrap();
+    let co2 = usize::from_str_radix(&ratings.co2, 2).unwrap();
+    o2 * co2
+}
+
+pub fn ones_more_equal(all: usize, ones: usize) -> bool {
     let zeroes = all - ones;
     ones >= zeroes
 }
 
-fn zeroes_more(all: usize, ones: usize) -> bool {
+pub fn zeroes_more(all: usize, ones: usize) -> bool {
     !ones_more_equal(all, ones)
 }
 
+pub fn ones_less_equal(all: usize, ones: usize) -> bool {
+    let zeroes = all - ones;
+    ones <= zeroes
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -96,4 +205,58 @@ mod tests {
         let input = include_str!("../test");
         assert_eq!(solve2(input), 230);
     }
+
+    #[test]
+    fn test_wide_report() {
+        let input = format!(
+            "{}\n{}\n{}\n",
+            "10".repeat(40),
+            "11".repeat(40),
+            "00".repeat(40)
+        );
+        let rates = power_rates(&input).unwrap();
+        assert_eq!(rates.gamma, "10".repeat(40));
+        assert_eq!(rates.epsilon, "01".repeat(40));
+
+        let ratings = life_support_ratings(&input).unwrap();
+        assert_eq!(ratings.oxygen, "11".repeat(40));
+        assert_eq!(ratings.co2, "00".repeat(40));
+    }
+
+    #[test]
+    fn test_rating() {
+        let input = include_str!("../test");
+        let report = Report::new(input).unwrap();
+        assert_eq!(report.oxygen(), "10111");
+        assert_eq!(report.co2(), "01010");
+        assert_eq!(report.rating(ones_less_equal), "01111");
+        assert_eq!(report.rating(|_, _| true), "11110");
+        assert_eq!(report.rating(|_, _| false), "00010");
+
+        let report = Report::new("0100\n0101\n").unwrap();
+        assert_eq!(report.co2(), "0100");
+        assert_eq!(report.rating(|_, _| true), "0101");
+    }
+
+    #[test]
+    fn test_invalid_report() {
+        assert_eq!(power_rates(""), Err(ReportError::Empty));
+        assert_eq!(power_rates("\n\n"), Err(ReportError::Empty));
+        assert_eq!(
+            power_rates("0101\n0121\n"),
+            Err(ReportError::InvalidCharacter {
+                line: 2,
+                column: 3,
+                found: '2'
+            })
+        );
+        assert_eq!(
+            life_support_ratings("0101\n010\n"),
+            Err(ReportError::WidthMismatch {
+                line: 2,
+                expected: 4,
+                found: 3
+            })
+        );
+    }
 }
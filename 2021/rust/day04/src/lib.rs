@@ -1,141 +1,304 @@
-const BOARD_ROWS: usize = 5;
-const BOARD_COLS: usize = 5;
-const BOARD_SIZE: usize = BOARD_ROWS * BOARD_COLS;
+use std::collections::HashMap;
+use std::fmt;
 
-#[derive(Debug)]
-enum Number {
-    Marked(u8),
-    Unmarked(u8),
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WinCondition {
+    Lines(usize),
+    FullCard,
 }
 
-type Board = Vec<Number>;
-
-fn parse_values(input: &str) -> (Vec<u8>, Vec<Board>) {
-    let mut it = input.split_ascii_whitespace();
+// Diagonals only exist on square boards and are ignored on any other shape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BingoRules {
+    pub diagonals: bool,
+    pub win: WinCondition,
+}
 
-    let numbers: Vec<u8> = it
-        .next()
-        .unwrap()
-        .split(',')
-        .filter_map(|x| x.parse().ok())
-        .collect();
-
-    let mut boards: Vec<Board> = Vec::new();
-    loop {
-        let board: Board = it
-            .by_ref()
-            .take(BOARD_SIZE)
-            .filter_map(|x| x.parse().ok())
-            .map(Number::Unmarked)
-            .collect();
-
-        if board.len() != BOARD_SIZE {
-            break;
+impl Default for BingoRules {
+    fn default() -> BingoRules {
+        BingoRules {
+            diagonals: false,
+            win: WinCondition::Lines(1),
         }
-
-        boards.push(board);
     }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Line {
+    Row(usize),
+    Column(usize),
+    Diagonal,
+    AntiDiagonal,
+}
 
-    (numbers, boards)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Win {
+    pub board: usize,
+    pub draw: usize,
+    pub number: u32,
+    pub score: u64,
+    pub line: Line,
 }
 
-pub fn solve1(input: &str) -> usize {
-    let (numbers, mut boards) = parse_values(input);
-    for number in numbers.into_iter() {
-        for board in boards.iter_mut() {
-            if let Some(position) = find_unmarked_number(board, number) {
-                board[position] = Number::Marked(number);
+// Wins are kept in finishing order; boards winning on the same draw are
+// ordered by their position in the input.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Game {
+    pub wins: Vec<Win>,
+    pub never_won: Vec<usize>,
+}
 
-                let (row, col) = (position / BOARD_COLS, position % BOARD_ROWS);
+impl Game {
+    pub fn first(&self) -> Option<&Win> {
+        self.wins.first()
+    }
 
-                let start = row * BOARD_ROWS;
-                let end = start + BOARD_COLS;
-                debug_assert_eq!((start..end).count(), BOARD_COLS);
+    pub fn last(&self) -> Option<&Win> {
+        self.wins.last()
+    }
 
-                let row_marked = (start..end).all(|i| matches!(board[i], Number::Marked(_)));
+    // `k` is zero-based, so `kth(0)` is the first winner.
+    pub fn kth(&self, k: usize) -> Option<&Win> {
+        self.wins.get(k)
+    }
 
-                let start = col;
-                let end = BOARD_SIZE;
-                debug_assert_eq!((start..end).step_by(BOARD_COLS).count(), BOARD_ROWS);
+    pub fn board(&self, board: usize) -> Option<&Win> {
+        self.wins.iter().find(|x| x.board == board)
+    }
+}
 
-                let col_marked = (start..end)
-                    .step_by(BOARD_COLS)
-                    .all(|i| matches!(board[i], Number::Marked(_)));
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoardError {
+    MissingDraws,
+    InvalidNumber {
+        line: usize,
+        text: String,
+    },
+    RaggedBoard {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+}
 
-                if row_marked || col_marked {
-                    let sum: usize = board
-                        .iter()
-                        .filter_map(|x| match x {
-                            Number::Unmarked(v) => Some(*v as usize),
-                            Number::Marked(_) => None,
-                        })
-                        .sum();
-                    return number as usize * sum;
-                }
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardError::MissingDraws => write!(f, "input has no line of drawn numbers"),
+            BoardError::InvalidNumber { line, text } => {
+                write!(f, "line {line}: `{text}` is not a number")
             }
+            BoardError::RaggedBoard {
+                line,
+                expected,
+                found,
+            } => write!(f, "line {line}: expected {expected} numbers, found {found}"),
         }
     }
+}
 
-    0
+impl std::error::Error for BoardError {}
+
+// Hit counters per line let a draw update a board in O(1) per marked cell.
+#[derive(Debug)]
+struct Board {
+    rows: usize,
+    cols: usize,
+    numbers: Vec<u32>,
+    marked: Vec<bool>,
+    marked_count: usize,
+    unmarked_sum: u64,
+    row_hits: Vec<usize>,
+    col_hits: Vec<usize>,
+    diagonal_hits: usize,
+    anti_diagonal_hits: usize,
+    complete_lines: usize,
 }
 
-fn find_unmarked_number(board: &[Number], number: u8) -> Option<usize> {
-    board.iter().position(|x| {
-        if let Number::Unmarked(v) = x {
-            *v == number
-        } else {
-            false
+impl Board {
+    fn new(rows: usize, cols: usize, numbers: Vec<u32>) -> Board {
+        Board {
+            rows,
+            cols,
+            marked: vec![false; numbers.len()],
+            marked_count: 0,
+            unmarked_sum: numbers.iter().map(|&x| x as u64).sum(),
+            numbers,
+            row_hits: vec![0; rows],
+            col_hits: vec![0; cols],
+            diagonal_hits: 0,
+            anti_diagonal_hits: 0,
+            complete_lines: 0,
         }
+    }
+
+    // Marks a single cell and returns the lowest line it completed, if any.
+    fn mark(&mut self, cell: usize, rules: &BingoRules) -> Option<Line> {
+        if self.marked[cell] {
+            return None;
+        }
+        self.marked[cell] = true;
+        self.marked_count += 1;
+        self.unmarked_sum -= self.numbers[cell] as u64;
+
+        let (row, col) = (cell / self.cols, cell % self.cols);
+        let mut completed = None;
+        let mut hit = |hits: &mut usize, len: usize, line: Line| {
+            *hits += 1;
+            if *hits == len {
+                self.complete_lines += 1;
+                completed = completed.or(Some(line));
+            }
+        };
+
+        hit(&mut self.row_hits[row], self.cols, Line::Row(row));
+        hit(&mut self.col_hits[col], self.rows, Line::Column(col));
+        if rules.diagonals && self.rows == self.cols {
+            if row == col {
+                hit(&mut self.diagonal_hits, self.rows, Line::Diagonal);
+            }
+            if row + col == self.cols - 1 {
+                hit(&mut self.anti_diagonal_hits, self.rows, Line::AntiDiagonal);
+            }
+        }
+        completed
+    }
+
+    fn has_won(&self, rules: &BingoRules) -> bool {
+        match rules.win {
+            WinCondition::Lines(n) => self.complete_lines >= n,
+            WinCondition::FullCard => self.marked_count == self.numbers.len(),
+        }
+    }
+
+    fn score(&self, number: u32) -> u64 {
+        number as u64 * self.unmarked_sum
+    }
+}
+
+fn parse_number(line: usize, text: &str) -> Result<u32, BoardError> {
+    text.trim().parse().map_err(|_| BoardError::InvalidNumber {
+        line,
+        text: text.to_string(),
     })
 }
 
-pub fn solve2(input: &str) -> usize {
-    let (numbers, mut boards) = parse_values(input);
-    for number in numbers.into_iter() {
-        let mut winners: Vec<usize> = Vec::new();
-        for (i, board) in boards.iter_mut().enumerate() {
-            if let Some(position) = find_unmarked_number(board, number) {
-                board[position] = Number::Marked(number);
+// `block` holds the board's lines with their one-based line numbers.
+fn parse_board(block: &[(usize, &str)]) -> Result<Board, BoardError> {
+    let mut numbers = Vec::new();
+    let mut cols = None;
+    for &(line, text) in block {
+        let row = text
+            .split_ascii_whitespace()
+            .map(|x| parse_number(line, x))
+            .collect::<Result<Vec<u32>, _>>()?;
+        let expected = *cols.get_or_insert(row.len());
+        if row.len() != expected {
+            return Err(BoardError::RaggedBoard {
+                line,
+                expected,
+                found: row.len(),
+            });
+        }
+        numbers.extend(row);
+    }
 
-                let (row, col) = (position / BOARD_COLS, position % BOARD_ROWS);
+    Ok(Board::new(block.len(), cols.unwrap_or(0), numbers))
+}
 
-                let start = row * BOARD_ROWS;
-                let end = start + BOARD_COLS;
-                debug_assert_eq!((start..end).count(), BOARD_COLS);
+fn parse_values(input: &str) -> Result<(Vec<u32>, Vec<Board>), BoardError> {
+    let mut it = input.lines().enumerate().map(|(i, line)| (i + 1, line));
 
-                let row_marked = (start..end).all(|i| matches!(board[i], Number::Marked(_)));
+    let numbers = it
+        .next()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .ok_or(BoardError::MissingDraws)
+        .and_then(|(line, text)| {
+            text.split(',')
+                .map(|x| parse_number(line, x))
+                .collect::<Result<Vec<u32>, _>>()
+        })?;
 
-                let start = col;
-                let end = BOARD_SIZE;
-                debug_assert_eq!((start..end).step_by(BOARD_COLS).count(), BOARD_ROWS);
+    let lines: Vec<(usize, &str)> = it.collect();
+    let boards = lines
+        .split(|(_, line)| line.trim().is_empty())
+        .filter(|block| !block.is_empty())
+        .map(parse_board)
+        .collect::<Result<_, _>>()?;
 
-                let col_marked = (start..end)
-                    .step_by(BOARD_COLS)
-                    .all(|i| matches!(board[i], Number::Marked(_)));
+    Ok((numbers, boards))
+}
 
-                if row_marked || col_marked {
-                    winners.push(i);
-                }
-            }
+// Maps every number to the cells holding it, ordered by board.
+fn index_boards(boards: &[Board]) -> HashMap<u32, Vec<(usize, usize)>> {
+    let mut index: HashMap<u32, Vec<(usize, usize)>> = HashMap::new();
+    for (i, board) in boards.iter().enumerate() {
+        for (cell, &number) in board.numbers.iter().enumerate() {
+            index.entry(number).or_default().push((i, cell));
         }
+    }
+    index
+}
 
-        if boards.len() == winners.len() {
-            let sum: usize = boards[winners.pop().unwrap()]
-                .iter()
-                .filter_map(|x| match x {
-                    Number::Unmarked(v) => Some(*v as usize),
-                    Number::Marked(_) => None,
-                })
-                .sum();
-            return number as usize * sum;
+pub fn play(input: &str, rules: &BingoRules) -> Result<Game, BoardError> {
+    let (numbers, mut boards) = parse_values(input)?;
+    let mut index = index_boards(&boards);
+    let mut won = vec![false; boards.len()];
+    let mut game = Game::default();
+
+    for (draw, number) in numbers.into_iter().enumerate() {
+        if game.wins.len() == boards.len() {
+            break;
         }
+        let Some(cells) = index.remove(&number) else {
+            continue;
+        };
 
-        while let Some(v) = winners.pop() {
-            boards.swap_remove(v);
+        for group in cells.chunk_by(|a, b| a.0 == b.0) {
+            let i = group[0].0;
+            if won[i] {
+                continue;
+            }
+            let board = &mut boards[i];
+            let line = group
+                .iter()
+                .filter_map(|&(_, cell)| board.mark(cell, rules))
+                .min();
+            if let Some(line) = line.filter(|_| board.has_won(rules)) {
+                won[i] = true;
+                game.wins.push(Win {
+                    board: i,
+                    draw,
+                    number,
+                    score: board.score(number),
+                    line,
+                });
+            }
         }
     }
 
-    0
+    game.never_won = (0..boards.len()).filter(|&i| !won[i]).collect();
+    Ok(game)
+}
+
+pub fn first_winner_score(input: &str, rules: &BingoRules) -> Result<Option<u64>, BoardError> {
+    Ok(play(input, rules)?.first().map(|x| x.score))
+}
+
+pub fn last_winner_score(input: &str, rules: &BingoRules) -> Result<Option<u64>, BoardError> {
+    Ok(play(input, rules)?.last().map(|x| x.score))
+}
+
+pub fn solve1(input: &str) -> u64 {
+    first_winner_score(input, &BingoRules::default())
+        .unwrap()
+        .unwrap_or(0)
+}
+
+pub fn solve2(input: &str) -> u64 {
+    last_winner_score(input, &BingoRules::default())
+        .unwrap()
+        .unwrap_or(0)
 }
 
 #[cfg(test)]
@@ -153,4 +316,95 @@ mod tests {
         let input = include_str!("../test");
         assert_eq!(solve2(input), 1924);
     }
+
+    #[test]
+    fn test_play() {
+        let input = include_str!("../test");
+        let game = play(input, &BingoRules::default()).unwrap();
+        assert_eq!(
+            game.first(),
+            Some(&Win {
+                board: 2,
+                draw: 11,
+                number: 24,
+                score: 4512,
+                line: Line::Row(0),
+            })
+        );
+        assert_eq!(game.kth(1).map(|x| x.board), Some(0));
+        assert_eq!(game.last().map(|x| (x.board, x.number)), Some((1, 13)));
+        assert_eq!(game.board(1).map(|x| x.line), Some(Line::Column(2)));
+        assert!(game.never_won.is_empty());
+
+        let game = play("5,1\n\n1 2\n3 4\n\n5 6\n7 8\n", &BingoRules::default()).unwrap();
+        assert_eq!(game.wins.len(), 0);
+        assert_eq!(game.never_won, [0, 1]);
+    }
+
+    #[test]
+    fn test_large_numbers() {
+        let input = "1000000,70000,300\n\n300 70000\n1000000 5\n\n70000 9\n8 1000000\n";
+        let game = play(input, &BingoRules::default()).unwrap();
+        assert_eq!(
+            game.first().map(|x| (x.board, x.number, x.score)),
+            Some((0, 300, 300 * 5))
+        );
+        assert_eq!(game.never_won, [1]);
+    }
+
+    #[test]
+    fn test_rules() {
+        let input = "1,5,9,2,3,4,6,7,8\n\n1 2 3\n4 5 6\n7 8 9\n\n9 8\n7 6\n";
+        let diagonals = BingoRules {
+            diagonals: true,
+            ..BingoRules::default()
+        };
+        assert_eq!(
+            first_winner_score(input, &BingoRules::default()),
+            Ok(Some(3 * 25))
+        );
+        assert_eq!(first_winner_score(input, &diagonals), Ok(Some(9 * 30)));
+        assert_eq!(last_winner_score(input, &diagonals), Ok(Some(6 * (8 + 7))));
+
+        let two_lines = BingoRules {
+            diagonals: true,
+            win: WinCondition::Lines(2),
+        };
+        assert_eq!(first_winner_score(input, &two_lines), Ok(Some(3 * 25)));
+
+        let full_card = BingoRules {
+            diagonals: false,
+            win: WinCondition::FullCard,
+        };
+        assert_eq!(first_winner_score(input, &full_card), Ok(Some(0)));
+        assert_eq!(first_winner_score("1\n\n1 2\n", &full_card), Ok(None));
+    }
+
+    #[test]
+    fn test_errors() {
+        let rules = BingoRules::default();
+        assert_eq!(play("", &rules), Err(BoardError::MissingDraws));
+        assert_eq!(
+            play("1\n\n1 2\n3\n", &rules),
+            Err(BoardError::RaggedBoard {
+                line: 4,
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            play("1,x\n\n1 2\n", &rules),
+            Err(BoardError::InvalidNumber {
+                line: 1,
+                text: "x".to_string()
+            })
+        );
+        assert_eq!(
+            last_winner_score("1\n\n1 2\n\n3 -4\n", &rules),
+            Err(BoardError::InvalidNumber {
+                line: 5,
+                text: "-4".to_string()
+            })
+        );
+    }
 }
@@ -1,6 +1,8 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt;
 
-#[derive(Eq, Hash, PartialEq, Debug)]
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Copy)]
 struct Point {
     x: i32,
     y: i32,
@@ -9,19 +11,23 @@ struct Point {
 struct Line(Point, Point);
 
 impl Line {
+    // Steps by `(dx, dy) / gcd(dx, dy)`, so only lattice points on the
+    // segment are produced whatever its slope.
     pub fn get_points(&self) -> Vec<Point> {
-        if self.is_horizontal() {
-            let y = self.0.y;
-            self.iter_x().map(|x| Point { x, y }).collect()
-        } else if self.is_vertical() {
-            let x = self.0.x;
-            self.iter_y().map(|y| Point { x, y }).collect()
-        } else {
-            self.iter_x()
-                .zip(self.iter_y())
-                .map(|(x, y)| Point { x, y })
-                .collect()
+        let (x0, y0) = (self.0.x as i64, self.0.y as i64);
+        let (dx, dy) = (self.1.x as i64 - x0, self.1.y as i64 - y0);
+        let steps = gcd(dx.abs(), dy.abs());
+        if steps == 0 {
+            return vec![self.0];
         }
+
+        let (sx, sy) = (dx / steps, dy / steps);
+        (0..=steps)
+            .map(|i| Point {
+                x: (x0 + i * sx) as i32,
+                y: (y0 + i * sy) as i32,
+            })
+            .collect()
     }
 
     pub fn is_diagonal(&self) -> bool {
@@ -36,23 +42,76 @@ impl Line {
         self.0.x == self.1.x
     }
 
-    fn iter_x(&self) -> Box<dyn Iterator<Item = i32>> {
-        if self.0.x < self.1.x {
-            Box::new(self.0.x..=self.1.x)
+    // Neither axis-aligned nor at exactly 45 degrees.
+    pub fn is_oblique(&self) -> bool {
+        let dx = (self.1.x as i64 - self.0.x as i64).abs();
+        let dy = (self.1.y as i64 - self.0.y as i64).abs();
+        self.is_diagonal() && dx != dy
+    }
+
+    // Lattice step between consecutive points, normalised so that parallel
+    // lines share it regardless of endpoint order. Single points are treated
+    // as horizontal.
+    fn direction(&self) -> (i64, i64) {
+        let dx = self.1.x as i64 - self.0.x as i64;
+        let dy = self.1.y as i64 - self.0.y as i64;
+        let steps = gcd(dx.abs(), dy.abs());
+        if steps == 0 {
+            return (1, 0);
+        }
+
+        let (dx, dy) = (dx / steps, dy / steps);
+        if dx < 0 || (dx == 0 && dy < 0) {
+            (-dx, -dy)
         } else {
-            Box::new((self.1.x..=self.0.x).rev())
+            (dx, dy)
         }
     }
+}
 
-    fn iter_y(&self) -> Box<dyn Iterator<Item = i32>> {
-        if self.0.y < self.1.y {
-            Box::new(self.0.y..=self.1.y)
-        } else {
-            Box::new((self.1.y..=self.0.y).rev())
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slopes {
+    Orthogonal,
+    Diagonal,
+    Any,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineError {
+    Malformed { line: usize, text: String },
+    UnsupportedSlope { line: usize, text: String },
+    MapTooLarge { width: u64, height: u64 },
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineError::Malformed { line, text } => {
+                write!(f, "line {line}: expected `x1,y1 -> x2,y2`, found `{text}`")
+            }
+            LineError::UnsupportedSlope { line, text } => {
+                write!(
+                    f,
+                    "line {line}: `{text}` is neither axis-aligned nor at 45 degrees"
+                )
+            }
+            LineError::MapTooLarge { width, height } => {
+                write!(f, "a {width}x{height} map is too large to allocate")
+            }
         }
     }
 }
 
+impl std::error::Error for LineError {}
+
 fn parse_point(s: &str) -> Option<Point> {
     let (x, y) = s.split_once(',')?;
     Some(Point {
@@ -69,33 +128,455 @@ fn parse_pair(s: &str) -> Option<Line> {
     ))
 }
 
-fn parse_values(input: &str) -> Vec<Line> {
-    input.lines().filter_map(parse_pair).collect()
+// Lines are paired with their one-based line numbers; blank lines are skipped.
+fn parse_values(input: &str) -> Result<Vec<(usize, Line)>, LineError> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, text)| !text.trim().is_empty())
+        .map(|(i, text)| {
+            let line = parse_pair(text).ok_or_else(|| LineError::Malformed {
+                line: i + 1,
+                text: text.to_string(),
+            })?;
+            Ok((i + 1, line))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    HashMap,
+    DenseGrid,
+    SweepLine,
+}
+
+fn overlap_count(values: &[Line], backend: Backend) -> usize {
+    match backend {
+        Backend::HashMap => overlap_count_hash_map(values),
+        Backend::DenseGrid => overlap_count_dense_grid(values),
+        Backend::SweepLine => overlap_count_sweep_line(values),
+    }
 }
 
-fn overlap_count(values: Vec<Line>) -> usize {
-    let mut map: HashMap<Point, i32> = HashMap::new();
+fn point_counts(values: &[Line]) -> HashMap<Point, u32> {
+    let mut map: HashMap<Point, u32> = HashMap::new();
     for p in values.iter().flat_map(Line::get_points) {
-        if let Some(v) = map.get_mut(&p) {
-            *v += 1;
+        *map.entry(p).or_insert(0) += 1;
+    }
+    map
+}
+
+fn overlap_count_hash_map(values: &[Line]) -> usize {
+    point_counts(values)
+        .into_values()
+        .filter(|x| *x > 1)
+        .count()
+}
+
+// Largest bounding box, in cells, that is ever allocated as a grid. The
+// dense grid backend falls back to the hash map above it.
+const GRID_LIMIT: usize = 1 << 26;
+
+fn overlap_count_dense_grid(values: &[Line]) -> usize {
+    let points = || values.iter().flat_map(|x| [&x.0, &x.1]);
+    let (Some(min_x), Some(max_x)) = (points().map(|p| p.x).min(), points().map(|p| p.x).max())
+    else {
+        return 0;
+    };
+    let min_y = points().map(|p| p.y).min().unwrap();
+    let max_y = points().map(|p| p.y).max().unwrap();
+
+    let width = (max_x as i64 - min_x as i64) as usize + 1;
+    let height = (max_y as i64 - min_y as i64) as usize + 1;
+    let Some(cells) = width.checked_mul(height).filter(|x| *x <= GRID_LIMIT) else {
+        return overlap_count_hash_map(values);
+    };
+
+    let mut grid = vec![0u8; cells];
+    for p in values.iter().flat_map(Line::get_points) {
+        let i = (p.y as i64 - min_y as i64) as usize * width + (p.x as i64 - min_x as i64) as usize;
+        grid[i] = grid[i].saturating_add(1);
+    }
+    grid.into_iter().filter(|x| *x > 1).count()
+}
+
+// Segments on the same supporting line, with positions measured as
+// `a * x + b * y` for direction `(a, b)`; consecutive lattice points differ
+// by `a * a + b * b`. Reduced directions of arbitrary slopes can reach 2^32
+// per component, so positions need i128.
+struct Group {
+    direction: (i128, i128),
+    origin: (i128, i128),
+    union: Vec<(i128, i128)>,
+    multiple: Vec<(i128, i128)>,
+}
+
+impl Group {
+    fn step(&self) -> i128 {
+        self.direction.0 * self.direction.0 + self.direction.1 * self.direction.1
+    }
+
+    fn position(&self, (x, y): (i128, i128)) -> i128 {
+        self.direction.0 * x + self.direction.1 * y
+    }
+
+    fn point(&self, position: i128) -> (i128, i128) {
+        let t = (position - self.position(self.origin)) / self.step();
+        (
+            self.origin.0 + t * self.direction.0,
+            self.origin.1 + t * self.direction.1,
+        )
+    }
+
+    fn contains(intervals: &[(i128, i128)], position: i128) -> bool {
+        let i = intervals.partition_point(|&(_, hi)| hi < position);
+        intervals.get(i).is_some_and(|&(lo, _)| lo <= position)
+    }
+}
+
+type Segment = ((i128, i128), (i128, i128));
+
+fn build_groups(values: &[Line]) -> Vec<Group> {
+    let mut segments: HashMap<((i128, i128), i128), Vec<Segment>> = HashMap::new();
+    for line in values.iter() {
+        let (a, b) = line.direction();
+        let (a, b) = (a as i128, b as i128);
+        let p0 = (line.0.x as i128, line.0.y as i128);
+        let p1 = (line.1.x as i128, line.1.y as i128);
+        // Points on the same supporting line share `b * x - a * y`.
+        let key = ((a, b), b * p0.0 - a * p0.1);
+        segments.entry(key).or_default().push((p0, p1));
+    }
+
+    segments
+        .into_iter()
+        .map(|((direction, _), segments)| {
+            let mut group = Group {
+                direction,
+                origin: segments[0].0,
+                union: Vec::new(),
+                multiple: Vec::new(),
+            };
+            let step = group.step();
+
+            let mut events: Vec<(i128, i32)> = Vec::new();
+            for &(p0, p1) in segments.iter() {
+                let (s0, s1) = (group.position(p0), group.position(p1));
+                events.push((s0.min(s1), 1));
+                events.push((s0.max(s1) + step, -1));
+            }
+            events.sort_unstable();
+
+            let mut coverage = 0;
+            let mut union_start = 0;
+            let mut multiple_start = 0;
+            for (position, delta) in events {
+                let before = coverage;
+                coverage += delta;
+                if before == 0 && coverage > 0 {
+                    union_start = position;
+                } else if before > 0 && coverage == 0 {
+                    group.union.push((union_start, position - step));
+                }
+                if before < 2 && coverage >= 2 {
+                    multiple_start = position;
+                } else if before >= 2 && coverage < 2 {
+                    group.multiple.push((multiple_start, position - step));
+                }
+            }
+            group
+        })
+        .collect()
+}
+
+// One merged interval of a group, with `start` its leftmost point, or its
+// lowest for vertical pieces.
+struct Piece {
+    group: usize,
+    direction: (i128, i128),
+    start: (i128, i128),
+    end: (i128, i128),
+}
+
+impl Piece {
+    // Height at column `x` as a fraction over `direction.0`; only meaningful
+    // for pieces that are not vertical.
+    fn height(&self, x: i128) -> i128 {
+        self.start.1 * self.direction.0 + (x - self.start.0) * self.direction.1
+    }
+
+    // Orders by height at column `x`, with ties broken by the order just
+    // left or just right of it.
+    fn cmp_at(&self, other: &Piece, x: i128, left: bool) -> Ordering {
+        let height =
+            (self.height(x) * other.direction.0).cmp(&(other.height(x) * self.direction.0));
+        let slope =
+            (self.direction.1 * other.direction.0).cmp(&(other.direction.1 * self.direction.0));
+        height.then(if left { slope.reverse() } else { slope })
+    }
+
+    // The crossing of two non-parallel, non-vertical pieces, if it is a
+    // lattice point.
+    fn crossing(&self, other: &Piece) -> Option<(i128, i128)> {
+        let (a, b) = self.direction;
+        let (c, d) = other.direction;
+        let numerator =
+            a * c * (other.start.1 - self.start.1) + self.start.0 * b * c - other.start.0 * d * a;
+        let denominator = b * c - d * a;
+        if numerator % denominator != 0 {
+            return None;
+        }
+        let x = numerator / denominator;
+        let height = self.height(x);
+        (height % a == 0).then_some((x, height / a))
+    }
+}
+
+// Calls `swapped` on every pair of adjacent items that trade places, so on
+// nearly sorted input the cost is linear plus the number of swaps.
+fn insertion_sort<T: Copy>(
+    items: &mut [T],
+    less: impl Fn(T, T) -> bool,
+    mut swapped: impl FnMut(T, T),
+) {
+    for i in 1..items.len() {
+        let mut j = i;
+        while j > 0 && less(items[j], items[j - 1]) {
+            swapped(items[j - 1], items[j]);
+            items.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+// Overlapping collinear segments are merged per supporting line by sorting
+// their endpoints, so points covered by different lines can only be single
+// crossings. Those are found by sweeping across the columns where merged
+// pieces start or end: the active pieces are kept in order of height, and
+// each adjacent swap needed to restore that order at the next column is a
+// crossing in between. Crossings on a column itself, including those with
+// vertical pieces, are found by comparing heights at that column.
+fn overlap_count_sweep_line(values: &[Line]) -> usize {
+    let groups = build_groups(values);
+
+    let mut total: i128 = groups
+        .iter()
+        .flat_map(|g| {
+            g.multiple
+                .iter()
+                .map(move |&(lo, hi)| (hi - lo) / g.step() + 1)
+        })
+        .sum();
+
+    let mut pieces: Vec<Piece> = groups
+        .iter()
+        .enumerate()
+        .flat_map(|(i, g)| {
+            g.union.iter().map(move |&(lo, hi)| Piece {
+                group: i,
+                direction: g.direction,
+                start: g.point(lo),
+                end: g.point(hi),
+            })
+        })
+        .collect();
+    pieces.sort_unstable_by_key(|p| p.start.0);
+
+    let mut columns: Vec<i128> = pieces.iter().flat_map(|p| [p.start.0, p.end.0]).collect();
+    columns.sort_unstable();
+    columns.dedup();
+
+    let mut crossings: Vec<((i128, i128), usize)> = Vec::new();
+    let mut active: Vec<usize> = Vec::new();
+    let mut next = 0;
+    for x in columns {
+        insertion_sort(
+            &mut active,
+            |i, j| pieces[i].cmp_at(&pieces[j], x, true).is_lt(),
+            |i, j| {
+                if let Some(point) = pieces[i].crossing(&pieces[j]) {
+                    crossings.push((point, pieces[i].group));
+                    crossings.push((point, pieces[j].group));
+                }
+            },
+        );
+
+        let mut column: Vec<(i128, usize)> = active
+            .iter()
+            .map(|&i| &pieces[i])
+            .filter(|p| p.height(x) % p.direction.0 == 0)
+            .map(|p| (p.height(x) / p.direction.0, p.group))
+            .collect();
+        let mut vertical: Vec<(i128, i128, usize)> = Vec::new();
+        let first = next;
+        while next < pieces.len() && pieces[next].start.0 == x {
+            let p = &pieces[next];
+            if p.direction.0 == 0 {
+                vertical.push((p.start.1, p.end.1, p.group));
+            } else {
+                column.push((p.start.1, p.group));
+            }
+            next += 1;
+        }
+
+        // Pieces of one group never share a point, so any shared height is
+        // a crossing.
+        column.sort_unstable();
+        for run in column
+            .chunk_by(|a, b| a.0 == b.0)
+            .filter(|run| run.len() > 1)
+        {
+            crossings.extend(run.iter().map(|&(y, group)| ((x, y), group)));
+        }
+        for (lo, hi, group) in vertical {
+            let from = column.partition_point(|&(y, _)| y < lo);
+            let to = column.partition_point(|&(y, _)| y <= hi);
+            for &(y, other) in column[from..to].iter() {
+                crossings.push(((x, y), other));
+                crossings.push(((x, y), group));
+            }
+        }
+
+        active.extend((first..next).filter(|&i| pieces[i].direction.0 != 0));
+        active.retain(|&i| pieces[i].end.0 > x);
+        active.sort_by(|&i, &j| pieces[i].cmp_at(&pieces[j], x, false));
+    }
+
+    crossings.sort_unstable();
+    crossings.dedup();
+    for run in crossings.chunk_by(|a, b| a.0 == b.0) {
+        let multiple = run
+            .iter()
+            .filter(|&&(point, i)| Group::contains(&groups[i].multiple, groups[i].position(point)))
+            .count() as i128;
+        if multiple == 0 {
+            total += 1;
         } else {
-            map.insert(p, 1);
+            total -= multiple - 1;
         }
     }
-    map.into_values().filter(|x| *x > 1).count()
+    total as usize
 }
 
-pub fn solve1(input: &str) -> usize {
-    let values = parse_values(input);
-    let values = values.into_iter().filter(|x| !x.is_diagonal()).collect();
+// `Orthogonal` skips every other line, as in part 1, while `Diagonal`
+// rejects lines it cannot represent instead of skipping them.
+fn select_lines(input: &str, slopes: Slopes) -> Result<Vec<Line>, LineError> {
+    let mut values = parse_values(input)?;
+    match slopes {
+        Slopes::Orthogonal => values.retain(|(_, x)| !x.is_diagonal()),
+        Slopes::Diagonal => {
+            if let Some(&(line, _)) = values.iter().find(|(_, x)| x.is_oblique()) {
+                return Err(LineError::UnsupportedSlope {
+                    line,
+                    text: input.lines().nth(line - 1).unwrap().to_string(),
+                });
+            }
+        }
+        Slopes::Any => {}
+    }
+    Ok(values.into_iter().map(|(_, x)| x).collect())
+}
 
-    overlap_count(values)
+pub fn count_overlaps(input: &str, slopes: Slopes, backend: Backend) -> Result<usize, LineError> {
+    let values = select_lines(input, slopes)?;
+    Ok(overlap_count(&values, backend))
 }
 
-pub fn solve2(input: &str) -> usize {
-    let values = parse_values(input);
+// Per-point line counts over a bounding box that always includes the origin,
+// matching the layout of the puzzle's examples.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverlapMap {
+    pub min_x: i32,
+    pub min_y: i32,
+    pub width: usize,
+    pub height: usize,
+    pub counts: Vec<u32>,
+}
+
+impl OverlapMap {
+    // `None` outside the map.
+    pub fn get(&self, x: i32, y: i32) -> Option<u32> {
+        let offset = |v: i32, min: i32, len: usize| {
+            usize::try_from(v as i64 - min as i64)
+                .ok()
+                .filter(|x| *x < len)
+        };
+        let col = offset(x, self.min_x, self.width)?;
+        let row = offset(y, self.min_y, self.height)?;
+        Some(self.counts[row * self.width + col])
+    }
+
+    pub fn max(&self) -> u32 {
+        self.counts.iter().copied().max().unwrap_or(0)
+    }
+
+    // Counts above 9 don't fit a single digit and are shown as `#`.
+    pub fn to_ascii(&self) -> String {
+        let mut ascii = String::with_capacity((self.width + 1) * self.height);
+        for row in self.counts.chunks(self.width) {
+            for &count in row {
+                ascii.push(match count {
+                    0 => '.',
+                    1..=9 => char::from_digit(count, 10).unwrap(),
+                    _ => '#',
+                });
+            }
+            ascii.push('\n');
+        }
+        ascii
+    }
+
+    // Binary PPM, scaled from black through red and yellow to white at the
+    // highest count.
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut ppm = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        let max = self.max().max(1) as f64;
+        for &count in self.counts.iter() {
+            let heat = count as f64 / max * 3.0;
+            let channel = |offset: f64| ((heat - offset).clamp(0.0, 1.0) * 255.0).round() as u8;
+            ppm.extend([channel(0.0), channel(1.0), channel(2.0)]);
+        }
+        ppm
+    }
+}
+
+pub fn overlap_map(input: &str, slopes: Slopes) -> Result<OverlapMap, LineError> {
+    let values = select_lines(input, slopes)?;
+    let counts = point_counts(&values);
+
+    let xs = || counts.keys().map(|p| p.x).chain([0]);
+    let ys = || counts.keys().map(|p| p.y).chain([0]);
+    let (min_x, max_x) = (xs().min().unwrap(), xs().max().unwrap());
+    let (min_y, max_y) = (ys().min().unwrap(), ys().max().unwrap());
+
+    let width = (max_x as i64 - min_x as i64) as u64 + 1;
+    let height = (max_y as i64 - min_y as i64) as u64 + 1;
+    if width.saturating_mul(height) > GRID_LIMIT as u64 {
+        return Err(LineError::MapTooLarge { width, height });
+    }
+
+    let mut map = OverlapMap {
+        min_x,
+        min_y,
+        width: width as usize,
+        height: height as usize,
+        counts: vec![0; (width * height) as usize],
+    };
+    for (p, count) in counts {
+        let i =
+            (p.y as i64 - min_y as i64) as usize * map.width + (p.x as i64 - min_x as i64) as usize;
+        map.counts[i] = count;
+    }
+    Ok(map)
+}
 
-    overlap_count(values)
+pub fn solve1(input: &str) -> usize {
+    count_overlaps(input, Slopes::Orthogonal, Backend::HashMap).unwrap()
+}
+
+pub fn solve2(input: &str) -> usize {
+    count_overlaps(input, Slopes::Diagonal, Backend::HashMap).unwrap()
 }
 
 #[cfg(test)]
@@ -114,6 +595,162 @@ mod tests {
         assert_eq!(solve2(input), 12);
     }
 
+    #[test]
+    fn test_backends() {
+        let backends = [Backend::HashMap, Backend::DenseGrid, Backend::SweepLine];
+        let input = include_str!("../test");
+        for backend in backends {
+            assert_eq!(count_overlaps(input, Slopes::Orthogonal, backend), Ok(5));
+            assert_eq!(count_overlaps(input, Slopes::Diagonal, backend), Ok(12));
+        }
+
+        let mut seed: u64 = 42;
+        let mut next = |n: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((seed >> 33) % n) as i32
+        };
+        let mut input = String::new();
+        for _ in 0..200 {
+            let (x, y) = (next(40), next(40));
+            let len = next(15);
+            let (dx, dy) = match next(5) {
+                0 => (len, 0),
+                1 => (0, len),
+                2 => (len, len),
+                3 => (len, -len),
+                _ => (next(15) - 7, next(15) - 7),
+            };
+            input += &format!("{},{} -> {},{}\n", x, y, x + dx, y + dy);
+        }
+        for slopes in [Slopes::Orthogonal, Slopes::Any] {
+            let expected = count_overlaps(&input, slopes, Backend::HashMap);
+            for backend in backends {
+                assert_eq!(count_overlaps(&input, slopes, backend), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sweep_line_huge_coordinates() {
+        let input =
+            "0,0 -> 2000000000,0\n1000000000,0 -> 2000000000,0\n1500000000,-5 -> 1500000000,5\n";
+        assert_eq!(
+            count_overlaps(input, Slopes::Orthogonal, Backend::SweepLine),
+            Ok(1000000001)
+        );
+    }
+
+    #[test]
+    fn test_dense_grid_wide_box() {
+        let input = "-2000000000,0 -> -2000000000,5\n2000000000,0 -> 2000000000,3\n-2000000000,2 -> -2000000000,4\n";
+        assert_eq!(
+            count_overlaps(input, Slopes::Orthogonal, Backend::DenseGrid),
+            Ok(3)
+        );
+    }
+
+    #[test]
+    fn test_huge_oblique_lines() {
+        let input = "0,0 -> 2000000000,1999999999\n0,1 -> 1999999999,1999999999\n2000000000,1999999999 -> 0,0\n-2000000000,-1999999999 -> 0,0\n2000000000,1999999990 -> 2000000000,1999999999\n";
+        for backend in [Backend::HashMap, Backend::DenseGrid, Backend::SweepLine] {
+            assert_eq!(count_overlaps(input, Slopes::Any, backend), Ok(2));
+        }
+    }
+
+    #[test]
+    fn test_oblique_lines() {
+        let l = Line(Point { x: 0, y: 0 }, Point { x: 6, y: -4 });
+        assert_eq!(
+            l.get_points(),
+            vec![
+                Point { x: 0, y: 0 },
+                Point { x: 3, y: -2 },
+                Point { x: 6, y: -4 }
+            ]
+        );
+
+        // The crossing at (1.5, 1.5) is not a lattice point.
+        let input = "0,0 -> 3,3\n0,3 -> 3,0\n0,0 -> 4,2\n";
+        assert_eq!(
+            count_overlaps(input, Slopes::Diagonal, Backend::HashMap),
+            Err(LineError::UnsupportedSlope {
+                line: 3,
+                text: "0,0 -> 4,2".to_string()
+            })
+        );
+        assert_eq!(
+            count_overlaps(
+                "\n0,0 -> 3,3\n\n0,0 -> 4,2\n",
+                Slopes::Diagonal,
+                Backend::HashMap
+            ),
+            Err(LineError::UnsupportedSlope {
+                line: 4,
+                text: "0,0 -> 4,2".to_string()
+            })
+        );
+        for backend in [Backend::HashMap, Backend::DenseGrid, Backend::SweepLine] {
+            assert_eq!(count_overlaps(input, Slopes::Any, backend), Ok(2));
+        }
+
+        assert_eq!(
+            count_overlaps("0,0 -> 1,1\n\n0,0 => 1,1\n", Slopes::Any, Backend::HashMap),
+            Err(LineError::Malformed {
+                line: 3,
+                text: "0,0 => 1,1".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_overlap_map() {
+        let input = include_str!("../test");
+        let map = overlap_map(input, Slopes::Orthogonal).unwrap();
+        assert_eq!(
+            map.to_ascii(),
+            ".......1..\n..1....1..\n..1....1..\n.......1..\n.112111211\n\
+             ..........\n..........\n..........\n..........\n222111....\n"
+        );
+
+        let map = overlap_map(input, Slopes::Diagonal).unwrap();
+        assert_eq!(
+            map.to_ascii(),
+            "1.1....11.\n.111...2..\n..2.1.111.\n...1.2.2..\n.112313211\n\
+             ...1.2....\n..1...1...\n.1.....1..\n1.......1.\n222111....\n"
+        );
+        assert_eq!(map.get(4, 4), Some(3));
+        assert_eq!(map.get(10, 4), None);
+        assert_eq!(map.get(i32::MIN, 0), None);
+        assert_eq!(map.max(), 3);
+
+        let ppm = map.to_ppm();
+        assert!(ppm.starts_with(b"P6\n10 10\n255\n"));
+        assert_eq!(ppm.len(), "P6\n10 10\n255\n".len() + 3 * 100);
+        assert_eq!(ppm[ppm.len() - 3..], [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_overlap_map_too_large() {
+        let input = "-2000000000,0 -> -2000000000,1\n2000000000,0 -> 2000000000,1\n";
+        assert_eq!(
+            overlap_map(input, Slopes::Orthogonal),
+            Err(LineError::MapTooLarge {
+                width: 4000000001,
+                height: 2
+            })
+        );
+        let input = "0,0 -> 0,1\n1000000000,1000000000 -> 1000000000,1000000001\n";
+        assert_eq!(
+            overlap_map(input, Slopes::Orthogonal),
+            Err(LineError::MapTooLarge {
+                width: 1000000001,
+                height: 1000000002
+            })
+        );
+    }
+
     #[test]
     fn test_get_points() {
         let l = Line(Point { x: 1, y: 1 }, Point { x: 1, y: 3 });
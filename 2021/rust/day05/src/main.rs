@@ -1,5 +1,25 @@
+use std::env;
+use std::fs;
+
+use day05::Slopes;
+
 fn main() {
     let input = include_str!("../input");
     println!("{}", day05::solve1(input));
     println!("{}", day05::solve2(input));
+
+    let mut slopes = Slopes::Diagonal;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--no-diagonals" => slopes = Slopes::Orthogonal,
+            "--ascii" => print!("{}", day05::overlap_map(input, slopes).unwrap().to_ascii()),
+            "--ppm" => {
+                let path = args.next().expect("missing output path");
+                let map = day05::overlap_map(input, slopes).unwrap();
+                fs::write(path, map.to_ppm()).unwrap();
+            }
+            _ => panic!("unknown option `{arg}`, expected --no-diagonals, --ascii or --ppm"),
+        }
+    }
 }
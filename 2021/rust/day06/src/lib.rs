@@ -1,8 +1,102 @@
-const DAYS1: usize = 80;
-const DAYS2: usize = 256;
-const NUMBER_OF_TIMERS: usize = 9;
-const NEW_FISH_TIMER: usize = 8;
-const RESET_TIMER: usize = 6;
+use num_bigint::BigUint;
+use std::num::NonZeroUsize;
+
+const DAYS1: u64 = 80;
+const DAYS2: u64 = 256;
+
+// Newborn fish wait `maturation_delay` days before their timer starts at
+// `newborn_timer`. With `death_after` set, a fish dies instead of resetting
+// once it has spawned that many times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FishModel {
+    pub reset_timer: usize,
+    pub newborn_timer: usize,
+    pub maturation_delay: usize,
+    pub death_after: Option<NonZeroUsize>,
+}
+
+impl Default for FishModel {
+    fn default() -> FishModel {
+        FishModel {
+            reset_timer: 6,
+            newborn_timer: 8,
+            maturation_delay: 0,
+            death_after: None,
+        }
+    }
+}
+
+// Every fish is in one of `phases * cycles` states: a phase is either a day
+// of maturation or a timer value, and cycles count past spawns.
+struct Lifecycle {
+    model: FishModel,
+    phases: usize,
+    cycles: usize,
+}
+
+impl Lifecycle {
+    fn new(model: &FishModel, fish: &[usize]) -> Lifecycle {
+        let max_timer = fish
+            .iter()
+            .copied()
+            .chain([model.reset_timer, model.newborn_timer])
+            .max()
+            .unwrap();
+        Lifecycle {
+            model: *model,
+            phases: model.maturation_delay + max_timer + 1,
+            cycles: model.death_after.map_or(1, NonZeroUsize::get),
+        }
+    }
+
+    fn states(&self) -> usize {
+        self.phases * self.cycles
+    }
+
+    fn adult(&self, timer: usize, cycle: usize) -> usize {
+        cycle * self.phases + self.model.maturation_delay + timer
+    }
+
+    fn newborn(&self) -> usize {
+        if self.model.maturation_delay > 0 {
+            self.model.maturation_delay - 1
+        } else {
+            self.adult(self.model.newborn_timer, 0)
+        }
+    }
+
+    fn successors(&self, state: usize) -> Vec<usize> {
+        let (cycle, phase) = (state / self.phases, state % self.phases);
+        let delay = self.model.maturation_delay;
+        if phase < delay {
+            return match phase {
+                0 => vec![self.adult(self.model.newborn_timer, cycle)],
+                _ => vec![state - 1],
+            };
+        }
+
+        match phase - delay {
+            0 => {
+                let mut next = vec![self.newborn()];
+                match self.model.death_after {
+                    Some(n) if cycle + 1 >= n.get() => {}
+                    Some(_) => next.push(self.adult(self.model.reset_timer, cycle + 1)),
+                    None => next.push(self.adult(self.model.reset_timer, cycle)),
+                }
+                next
+            }
+            _ => vec![state - 1],
+        }
+    }
+
+    fn initial_counts(&self, fish: &[usize]) -> Vec<usize> {
+        let mut counts = vec![0; self.states()];
+        for &v in fish {
+            counts[self.adult(v, 0)] += 1;
+        }
+        counts
+    }
+}
 
 fn parse_values(input: &str) -> Vec<usize> {
     input
@@ -12,29 +106,170 @@ fn parse_values(input: &str) -> Vec<usize> {
         .collect()
 }
 
-fn calculate_fish(fish: &Vec<usize>, days: usize) -> usize {
-    let mut fish_counts = vec![0; NUMBER_OF_TIMERS];
-    for v in fish {
-        fish_counts[*v] += 1;
-    }
+// Total population on each day from `0` to `days`.
+fn time_series(model: &FishModel, fish: &[usize], days: u64) -> Vec<BigUint> {
+    let lifecycle = Lifecycle::new(model, fish);
+    let successors: Vec<Vec<usize>> = (0..lifecycle.states())
+        .map(|x| lifecycle.successors(x))
+        .collect();
 
+    let mut fish_counts: Vec<BigUint> = lifecycle
+        .initial_counts(fish)
+        .into_iter()
+        .map(BigUint::from)
+        .collect();
+    let mut series = vec![fish_counts.iter().sum()];
     for _ in 0..days {
-        let new_count = fish_counts[0];
-        fish_counts.copy_within(1.., 0);
-        fish_counts[RESET_TIMER] += new_count;
-        fish_counts[NEW_FISH_TIMER] = new_count;
+        let mut next = vec![BigUint::ZERO; fish_counts.len()];
+        for (state, count) in fish_counts.iter().enumerate() {
+            for &x in successors[state].iter() {
+                next[x] += count;
+            }
+        }
+        fish_counts = next;
+        series.push(fish_counts.iter().sum());
+    }
+    series
+}
+
+fn calculate_fish(model: &FishModel, fish: &[usize], days: u64) -> BigUint {
+    time_series(model, fish, days).pop().unwrap()
+}
+
+pub fn population(input: &str, model: &FishModel, days: u64) -> Vec<BigUint> {
+    let values = parse_values(input);
+    time_series(model, &values, days)
+}
+
+pub fn population_csv(series: &[BigUint]) -> String {
+    let mut csv = String::from("day,fish\n");
+    for (day, count) in series.iter().enumerate() {
+        csv += &format!("{day},{count}\n");
+    }
+    csv
+}
+
+trait Count: Clone {
+    fn with_value(&self, value: u64) -> Self;
+    fn add(&self, other: &Self) -> Self;
+    fn mul(&self, other: &Self) -> Self;
+}
+
+impl Count for BigUint {
+    fn with_value(&self, value: u64) -> Self {
+        BigUint::from(value)
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        self * other
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Modular {
+    value: u64,
+    modulus: u64,
+}
+
+impl Count for Modular {
+    fn with_value(&self, value: u64) -> Self {
+        Modular {
+            value: value % self.modulus,
+            modulus: self.modulus,
+        }
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        self.with_value(((self.value as u128 + other.value as u128) % self.modulus as u128) as u64)
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        self.with_value(((self.value as u128 * other.value as u128) % self.modulus as u128) as u64)
     }
-    fish_counts.into_iter().sum()
+}
+
+type Matrix<T> = Vec<Vec<T>>;
+
+// Entry `[i][j]` is how many fish in state `j` one fish in state `i`
+// becomes after a day.
+fn transition<T: Count>(lifecycle: &Lifecycle, unit: &T) -> Matrix<T> {
+    let mut matrix = vec![vec![unit.with_value(0); lifecycle.states()]; lifecycle.states()];
+    for (i, row) in matrix.iter_mut().enumerate() {
+        for j in lifecycle.successors(i) {
+            row[j] = row[j].add(&unit.with_value(1));
+        }
+    }
+    matrix
+}
+
+fn multiply<T: Count>(a: &Matrix<T>, b: &Matrix<T>) -> Matrix<T> {
+    a.iter()
+        .map(|row| {
+            (0..b[0].len())
+                .map(|j| {
+                    row.iter()
+                        .zip(b.iter())
+                        .map(|(x, b_row)| x.mul(&b_row[j]))
+                        .reduce(|acc, x| acc.add(&x))
+                        .unwrap()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Raises the transition matrix to `days` by repeated squaring, applying it to
+// the timer histogram along the way, so only O(log days) products are needed.
+fn simulate<T: Count>(model: &FishModel, fish: &[usize], days: u64, unit: &T) -> T {
+    let lifecycle = Lifecycle::new(model, fish);
+    let mut counts = vec![lifecycle
+        .initial_counts(fish)
+        .into_iter()
+        .map(|x| unit.with_value(x as u64))
+        .collect()];
+
+    let mut matrix = transition(&lifecycle, unit);
+    let mut days = days;
+    while days > 0 {
+        if days & 1 == 1 {
+            counts = multiply(&counts, &matrix);
+        }
+        days >>= 1;
+        if days > 0 {
+            matrix = multiply(&matrix, &matrix);
+        }
+    }
+    counts
+        .remove(0)
+        .into_iter()
+        .reduce(|acc, x| acc.add(&x))
+        .unwrap()
+}
+
+pub fn count_fish(input: &str, model: &FishModel, days: u64) -> BigUint {
+    let values = parse_values(input);
+    simulate(model, &values, days, &BigUint::from(1u32))
+}
+
+pub fn count_fish_modulo(input: &str, model: &FishModel, days: u64, modulus: u64) -> u64 {
+    assert!(modulus > 0, "modulus must be positive");
+    let values = parse_values(input);
+    let unit = Modular { value: 1, modulus };
+    simulate(model, &values, days, &unit).value
 }
 
 pub fn solve1(input: &str) -> usize {
     let values = parse_values(input);
-    calculate_fish(&values, DAYS1)
+    usize::try_from(calculate_fish(&FishModel::default(), &values, DAYS1)).unwrap()
 }
 
 pub fn solve2(input: &str) -> usize {
     let values = parse_values(input);
-    calculate_fish(&values, DAYS2)
+    usize::try_from(calculate_fish(&FishModel::default(), &values, DAYS2)).unwrap()
 }
 
 #[cfg(test)]
@@ -52,4 +287,59 @@ mod tests {
         let input = include_str!("../test");
         assert_eq!(solve2(input), 26984457539);
     }
+
+    #[test]
+    fn test_count_fish() {
+        let input = include_str!("../test");
+        let values = parse_values(input);
+        let model = FishModel::default();
+        for days in [0, 1, 18, 80, 255, 256] {
+            let expected = calculate_fish(&model, &values, days);
+            assert_eq!(count_fish(input, &model, days), expected);
+            assert_eq!(
+                BigUint::from(count_fish_modulo(input, &model, days, 1_000_000_007)),
+                expected % 1_000_000_007u32
+            );
+        }
+
+        let huge = count_fish(input, &model, 10_000);
+        assert_eq!(
+            BigUint::from(count_fish_modulo(input, &model, 10_000, u64::MAX)),
+            huge % u64::MAX
+        );
+        assert!(count_fish_modulo(input, &model, 1_000_000_000_000, 998_244_353) < 998_244_353);
+    }
+
+    #[test]
+    fn test_fish_model() {
+        let model = FishModel {
+            reset_timer: 2,
+            newborn_timer: 1,
+            maturation_delay: 2,
+            death_after: NonZeroUsize::new(2),
+        };
+        // The first fish spawns on days 1 and 4, dying on its second spawn.
+        // Its first child matures on days 2 and 3 and then spawns on day 5.
+        let series = population("0", &model, 6);
+        assert_eq!(series, [1u32, 2, 2, 2, 2, 3, 3].map(BigUint::from));
+        for days in 0..40 {
+            assert_eq!(
+                count_fish("0", &model, days),
+                population("0", &model, days)[days as usize]
+            );
+        }
+
+        let series = population(include_str!("../test"), &FishModel::default(), 18);
+        assert_eq!(series[0], BigUint::from(5u32));
+        assert_eq!(series[18], BigUint::from(26u32));
+        assert_eq!(population_csv(&series[..2]), "day,fish\n0,5\n1,5\n");
+    }
+
+    #[test]
+    fn test_population_overflow() {
+        let model = FishModel::default();
+        let series = population("3,4,3,1,2", &model, 600);
+        assert!(series[600] > BigUint::from(u64::MAX));
+        assert_eq!(series[600], count_fish("3,4,3,1,2", &model, 600));
+    }
 }
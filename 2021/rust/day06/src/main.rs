@@ -1,5 +1,24 @@
+use std::env;
+use std::fs;
+
+use day06::FishModel;
+
 fn main() {
     let input = include_str!("../input");
     println!("{}", day06::solve1(input));
     println!("{}", day06::solve2(input));
+
+    let mut days = 256;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().expect("missing option value");
+        match arg.as_str() {
+            "--days" => days = value.parse().expect("invalid number of days"),
+            "--csv" => {
+                let series = day06::population(input, &FishModel::default(), days);
+                fs::write(value, day06::population_csv(&series)).unwrap();
+            }
+            _ => panic!("unknown option `{arg}`, expected --days or --csv"),
+        }
+    }
 }
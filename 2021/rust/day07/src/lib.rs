@@ -1,3 +1,68 @@
+pub trait FuelCost {
+    fn cost(&self, distance: usize) -> usize;
+
+    // The total of a linear cost is minimised by the median, which lets the
+    // optimiser skip the search.
+    fn is_linear(&self) -> bool {
+        false
+    }
+}
+
+pub struct Linear;
+
+impl FuelCost for Linear {
+    fn cost(&self, distance: usize) -> usize {
+        distance
+    }
+
+    fn is_linear(&self) -> bool {
+        true
+    }
+}
+
+pub struct Triangular;
+
+impl FuelCost for Triangular {
+    fn cost(&self, distance: usize) -> usize {
+        distance * (distance + 1) / 2
+    }
+}
+
+pub struct Quadratic;
+
+impl FuelCost for Quadratic {
+    fn cost(&self, distance: usize) -> usize {
+        distance * distance
+    }
+}
+
+// Any convex cost function can be used directly.
+impl<F: Fn(usize) -> usize> FuelCost for F {
+    fn cost(&self, distance: usize) -> usize {
+        self(distance)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alignment {
+    pub position: usize,
+    pub cost: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crab {
+    pub position: usize,
+    pub weight: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiAlignment {
+    pub positions: Vec<usize>,
+    pub cost: usize,
+    // Index into `positions` for every crab, in input order.
+    pub assignment: Vec<usize>,
+}
+
 fn parse_values(input: &str) -> Vec<usize> {
     input
         .trim_end()
@@ -6,41 +71,260 @@ fn parse_values(input: &str) -> Vec<usize> {
         .collect()
 }
 
-pub fn solve1(input: &str) -> usize {
-    let mut values = parse_values(input);
+// Crabs are written as `position` or `position:weight`, with a default weight
+// of 1.
+fn parse_crabs(input: &str) -> Vec<Crab> {
+    input
+        .trim_end()
+        .split(',')
+        .filter_map(|x| {
+            let (position, weight) = x.split_once(':').unwrap_or((x, "1"));
+            Some(Crab {
+                position: position.trim().parse().ok()?,
+                weight: weight.trim().parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+fn median(values: &mut [usize]) -> usize {
+    // Both indices name the same element for odd lengths.
+    let low = (values.len() - 1) / 2;
+    let high = values.len() / 2;
+
+    let (_, &mut high_median, _) = values.select_nth_unstable(high);
+    let (_, &mut low_median, _) = values.select_nth_unstable(low);
+    (high_median + low_median) / 2
+}
+
+// Sorted by position with equal positions merged.
+fn compress(crabs: &[Crab]) -> Vec<Crab> {
+    let mut crabs = crabs.to_vec();
+    crabs.sort_unstable_by_key(|x| x.position);
+    let mut merged: Vec<Crab> = Vec::with_capacity(crabs.len());
+    for crab in crabs {
+        match merged.last_mut() {
+            Some(last) if last.position == crab.position => last.weight += crab.weight,
+            _ => merged.push(crab),
+        }
+    }
+    merged
+}
+
+fn weighted_median(crabs: &[Crab]) -> usize {
+    let crabs = compress(crabs);
+    let total: usize = crabs.iter().map(|x| x.weight).sum();
+    let mut seen = 0;
+    for crab in crabs.iter() {
+        seen += crab.weight;
+        if 2 * seen >= total {
+            return crab.position;
+        }
+    }
+    crabs[0].position
+}
+
+pub fn total_cost(values: &[usize], position: usize, cost: &impl FuelCost) -> usize {
+    values
+        .iter()
+        .fold(0, |acc, x| acc + cost.cost(x.abs_diff(position)))
+}
+
+// The total cost of a convex per-crab cost is itself convex in the position,
+// so binary searching for the first non-negative slope finds a minimum in
+// O(n log range).
+pub fn optimise(values: &[usize], cost: &impl FuelCost) -> Alignment {
+    let position = if cost.is_linear() {
+        median(&mut values.to_vec())
+    } else {
+        let mut low = *values.iter().min().unwrap();
+        let mut high = *values.iter().max().unwrap();
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if total_cost(values, mid + 1, cost) >= total_cost(values, mid, cost) {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        low
+    };
+
+    Alignment {
+        position,
+        cost: total_cost(values, position, cost),
+    }
+}
 
-    let median = if values.len() % 2 == 0 {
-        let high = values.len() / 2;
-        let low = high - 1;
+pub fn weighted_total_cost(crabs: &[Crab], position: usize, cost: &impl FuelCost) -> usize {
+    crabs.iter().fold(0, |acc, x| {
+        acc + x.weight * cost.cost(x.position.abs_diff(position))
+    })
+}
 
-        let (_, &mut high_median, _) = values.select_nth_unstable(high);
-        let (_, &mut low_median, _) = values.select_nth_unstable(low);
-        (high_median + low_median) / 2
+pub fn optimise_weighted(crabs: &[Crab], cost: &impl FuelCost) -> Alignment {
+    let position = if cost.is_linear() {
+        weighted_median(crabs)
     } else {
-        let mid = values.len() / 2;
+        let mut low = crabs.iter().map(|x| x.position).min().unwrap();
+        let mut high = crabs.iter().map(|x| x.position).max().unwrap();
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if weighted_total_cost(crabs, mid + 1, cost) >= weighted_total_cost(crabs, mid, cost) {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        low
+    };
+
+    Alignment {
+        position,
+        cost: weighted_total_cost(crabs, position, cost),
+    }
+}
+
+// Linear cost of gathering `crabs[i..=j]` at their weighted median, using
+// prefix sums of weights and weighted positions.
+struct Segments<'a> {
+    crabs: &'a [Crab],
+    weights: Vec<usize>,
+    moments: Vec<usize>,
+}
+
+impl<'a> Segments<'a> {
+    fn new(crabs: &'a [Crab]) -> Segments<'a> {
+        let mut weights = vec![0];
+        let mut moments = vec![0];
+        for crab in crabs.iter() {
+            weights.push(weights.last().unwrap() + crab.weight);
+            moments.push(moments.last().unwrap() + crab.weight * crab.position);
+        }
+        Segments {
+            crabs,
+            weights,
+            moments,
+        }
+    }
+
+    fn median(&self, i: usize, j: usize) -> usize {
+        let total = self.weights[j + 1] - self.weights[i];
+        let m =
+            i + self.weights[i + 1..=j + 1].partition_point(|&w| 2 * (w - self.weights[i]) < total);
+        self.crabs[m].position
+    }
+
+    fn cost(&self, i: usize, j: usize) -> (usize, usize) {
+        let position = self.median(i, j);
+        let m = self.crabs.partition_point(|x| x.position <= position);
+        let below =
+            position * (self.weights[m] - self.weights[i]) - (self.moments[m] - self.moments[i]);
+        let above = (self.moments[j + 1] - self.moments[m])
+            - position * (self.weights[j + 1] - self.weights[m]);
+        (position, below + above)
+    }
+}
 
-        let (_, &mut median, _) = values.select_nth_unstable(mid);
-        median
+// With a convex cost every crab goes to its nearest target, so each target
+// serves a contiguous run of sorted crabs and the best split into `k` runs is
+// found by dynamic programming over the distinct crab positions. The best
+// split point is monotone in the end of the run, so each layer is filled by
+// divide and conquer and only O(n log n) run costs are ever computed, from
+// prefix sums for a linear cost and by `optimise_weighted` otherwise. The
+// last layer only needs the runs ending at the last crab.
+pub fn optimise_k_targets(crabs: &[Crab], k: usize, cost: &impl FuelCost) -> MultiAlignment {
+    assert!(k > 0, "at least one target is required");
+    let sorted = compress(crabs);
+    let n = sorted.len();
+    let k = k.min(n);
+    let segments = Segments::new(&sorted);
+
+    // Best target and cost for `sorted[i..=j]`.
+    let run = |i: usize, j: usize| {
+        if cost.is_linear() {
+            segments.cost(i, j)
+        } else {
+            let alignment = optimise_weighted(&sorted[i..=j], cost);
+            (alignment.position, alignment.cost)
+        }
     };
 
-    values
-        .into_iter()
-        .fold(0, |acc, x| acc + x.abs_diff(median))
+    // best[t][j]: cost of serving the first `j` crabs with `t` targets.
+    let mut best = vec![vec![usize::MAX; n + 1]; k + 1];
+    let mut split = vec![vec![0; n + 1]; k + 1];
+    best[0][0] = 0;
+    for t in 1..=k {
+        let first = if t == k { n } else { t };
+        // (lo, hi, opt_lo, opt_hi): fill best[t][lo..=hi], splitting within
+        // opt_lo..=opt_hi.
+        let mut pending = vec![(first, n, t - 1, n - 1)];
+        while let Some((lo, hi, opt_lo, opt_hi)) = pending.pop() {
+            if lo > hi {
+                continue;
+            }
+            let j = lo + (hi - lo) / 2;
+            for i in opt_lo..=opt_hi.min(j - 1) {
+                if best[t - 1][i] == usize::MAX {
+                    continue;
+                }
+                let total = best[t - 1][i] + run(i, j - 1).1;
+                if total < best[t][j] {
+                    best[t][j] = total;
+                    split[t][j] = i;
+                }
+            }
+            pending.push((lo, j - 1, opt_lo, split[t][j]));
+            pending.push((j + 1, hi, split[t][j], opt_hi));
+        }
+    }
+
+    let mut positions = Vec::with_capacity(k);
+    let mut j = n;
+    for t in (1..=k).rev() {
+        let i = split[t][j];
+        positions.push(run(i, j - 1).0);
+        j = i;
+    }
+    positions.reverse();
+
+    let assignment = crabs
+        .iter()
+        .map(|crab| {
+            (0..positions.len())
+                .min_by_key(|&i| positions[i].abs_diff(crab.position))
+                .unwrap()
+        })
+        .collect();
+
+    MultiAlignment {
+        positions,
+        cost: best[k][n],
+        assignment,
+    }
 }
 
-pub fn solve2(input: &str) -> usize {
+pub fn align_weighted(input: &str, cost: &impl FuelCost) -> Alignment {
+    let crabs = parse_crabs(input);
+    optimise_weighted(&crabs, cost)
+}
+
+pub fn align_k_targets(input: &str, k: usize, cost: &impl FuelCost) -> MultiAlignment {
+    let crabs = parse_crabs(input);
+    optimise_k_targets(&crabs, k, cost)
+}
+
+pub fn align(input: &str, cost: &impl FuelCost) -> Alignment {
     let values = parse_values(input);
-    let max = *values.iter().max().unwrap();
-    let min = *values.iter().min().unwrap();
-    (min..=max)
-        .map(|x| {
-            values.iter().fold(0, |acc, v| {
-                let diff = v.abs_diff(x);
-                acc + diff * (diff + 1) / 2
-            })
-        })
-        .min()
-        .unwrap()
+    optimise(&values, cost)
+}
+
+pub fn solve1(input: &str) -> usize {
+    align(input, &Linear).cost
+}
+
+pub fn solve2(input: &str) -> usize {
+    align(input, &Triangular).cost
 }
 
 #[cfg(test)]
@@ -58,4 +342,99 @@ mod tests {
         let input = include_str!("../test");
         assert_eq!(solve2(input), 168);
     }
+
+    #[test]
+    fn test_optimise() {
+        let input = include_str!("../test");
+        assert_eq!(
+            align(input, &Linear),
+            Alignment {
+                position: 2,
+                cost: 37
+            }
+        );
+        assert_eq!(
+            align(input, &Triangular),
+            Alignment {
+                position: 5,
+                cost: 168
+            }
+        );
+
+        let values = parse_values(input);
+        let brute_force = |cost: &dyn Fn(usize) -> usize| {
+            (0..=16)
+                .map(|x| total_cost(&values, x, &cost))
+                .min()
+                .unwrap()
+        };
+        assert_eq!(align(input, &Quadratic).cost, brute_force(&|x| x * x));
+        let cubic = |x: usize| x * x * x;
+        assert_eq!(align(input, &cubic).cost, brute_force(&cubic));
+    }
+
+    #[test]
+    fn test_weighted() {
+        let input = include_str!("../test");
+        assert_eq!(align_weighted(input, &Linear).cost, 37);
+        assert_eq!(
+            align_weighted(input, &Triangular),
+            align(input, &Triangular)
+        );
+
+        let input = "0:1,10:3,4";
+        assert_eq!(
+            align_weighted(input, &Linear),
+            Alignment {
+                position: 10,
+                cost: 10 + 6
+            }
+        );
+        let crabs = parse_crabs(input);
+        let best = (0..=10)
+            .map(|x| weighted_total_cost(&crabs, x, &Triangular))
+            .min()
+            .unwrap();
+        assert_eq!(align_weighted(input, &Triangular).cost, best);
+    }
+
+    #[test]
+    fn test_k_targets() {
+        let input = include_str!("../test");
+        assert_eq!(align_k_targets(input, 1, &Linear).cost, 37);
+
+        let alignment = align_k_targets(input, 2, &Linear);
+        assert_eq!(alignment.positions, [2, 14]);
+        assert_eq!(alignment.cost, 13);
+        assert_eq!(alignment.assignment, [1, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+
+        let alignment = align_k_targets("1:5,2,9:2,10:2", 5, &Linear);
+        assert_eq!(alignment.positions, [1, 2, 9, 10]);
+        assert_eq!(alignment.cost, 0);
+
+        // Every pair of targets, each crab paying for the nearer one.
+        let crabs = parse_crabs(input);
+        let brute_force = |cost: &dyn Fn(usize) -> usize| {
+            let cost_at = |a: usize, b: usize| {
+                crabs
+                    .iter()
+                    .map(|x| {
+                        x.weight * cost(x.position.abs_diff(a)).min(cost(x.position.abs_diff(b)))
+                    })
+                    .sum::<usize>()
+            };
+            (0..=16)
+                .flat_map(|a| (a..=16).map(move |b| (a, b)))
+                .map(|(a, b)| cost_at(a, b))
+                .min()
+                .unwrap()
+        };
+        let triangular = align_k_targets(input, 2, &Triangular);
+        assert_eq!(triangular.cost, brute_force(&|d| d * (d + 1) / 2));
+        assert_eq!(triangular.assignment[0], triangular.assignment[9]);
+        assert_eq!(
+            align_k_targets(input, 2, &Quadratic).cost,
+            brute_force(&|d| d * d)
+        );
+    }
 }
@@ -1,90 +1,297 @@
-use std::collections::HashMap;
+use std::fmt;
 
-fn parse_input_output(input: &str) -> Option<(&str, &str)> {
-    input
-        .split_once('|')
-        .map(|(inp, outp)| (inp.trim_end(), outp.trim_start()))
+// Canonical segments lit for each digit of a seven-segment display.
+const SEVEN_SEGMENT_DIGITS: [(char, &str); 10] = [
+    ('0', "abcefg"),
+    ('1', "cf"),
+    ('2', "acdeg"),
+    ('3', "acdfg"),
+    ('4', "bcdf"),
+    ('5', "abdfg"),
+    ('6', "abdefg"),
+    ('7', "acf"),
+    ('8', "abcdefg"),
+    ('9', "abcdfg"),
+];
+
+// Segment names double as wire names, as scrambled wires are labelled with
+// the segment they should have driven.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayDefinition {
+    segments: Vec<char>,
+    symbols: Vec<(char, u32)>,
 }
 
-fn parse_values(input: &str) -> Vec<(&str, &str)> {
-    input
-        .lines()
-        .filter_map(|x| parse_input_output(x.trim_end()))
-        .collect()
+impl DisplayDefinition {
+    pub fn new(segments: &str, symbols: &[(char, &str)]) -> Result<DisplayDefinition, DecodeError> {
+        let mut definition = DisplayDefinition {
+            segments: segments.chars().collect(),
+            symbols: Vec::new(),
+        };
+        let mut sorted = definition.segments.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        if sorted.is_empty()
+            || sorted.len() != definition.segments.len()
+            || sorted.len() > u32::BITS as usize
+        {
+            return Err(DecodeError::InvalidDefinition(segments.to_string()));
+        }
+
+        for &(symbol, lit) in symbols.iter() {
+            let signal = definition
+                .signal(lit)
+                .filter(|x| definition.symbols.iter().all(|(_, y)| x != y))
+                .ok_or_else(|| DecodeError::InvalidDefinition(format!("{symbol}: {lit}")))?;
+            definition.symbols.push((symbol, signal));
+        }
+        Ok(definition)
+    }
+
+    pub fn seven_segment() -> DisplayDefinition {
+        DisplayDefinition::new("abcdefg", &SEVEN_SEGMENT_DIGITS).unwrap()
+    }
+
+    fn all(&self) -> u32 {
+        u32::MAX >> (u32::BITS as usize - self.segments.len())
+    }
+
+    // Rejects unknown wires as well as wires repeated within a pattern.
+    fn signal(&self, pattern: &str) -> Option<u32> {
+        let mut signal = 0;
+        for wire in pattern.chars() {
+            let bit = 1 << self.segments.iter().position(|&x| x == wire)?;
+            if signal & bit != 0 {
+                return None;
+            }
+            signal |= bit;
+        }
+        Some(signal)
+    }
+
+    fn signals(&self, input: &str) -> Result<Vec<u32>, DecodeError> {
+        input
+            .split_ascii_whitespace()
+            .map(|x| {
+                self.signal(x)
+                    .ok_or_else(|| DecodeError::InvalidPattern(x.to_string()))
+            })
+            .collect()
+    }
+
+    pub fn decode(&self, mapping: &WireMapping, pattern: &str) -> Option<char> {
+        let lit = mapping.map(self.signal(pattern)?);
+        self.symbols
+            .iter()
+            .find(|(_, x)| *x == lit)
+            .map(|(symbol, _)| *symbol)
+    }
 }
 
-fn pattern_to_signal(pattern: &str) -> Option<u8> {
-    let mut u8 = 0;
-    for segment in pattern.bytes() {
-        match segment {
-            b'a' => u8 |= 1,
-            b'b' => u8 |= 1 << 1,
-            b'c' => u8 |= 1 << 2,
-            b'd' => u8 |= 1 << 3,
-            b'e' => u8 |= 1 << 4,
-            b'f' => u8 |= 1 << 5,
-            b'g' => u8 |= 1 << 6,
-            _ => return None,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    MissingSeparator,
+    InvalidDefinition(String),
+    InvalidPattern(String),
+    Impossible,
+    Ambiguous,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::MissingSeparator => write!(f, "expected `patterns | output`"),
+            DecodeError::InvalidDefinition(x) => write!(f, "invalid display definition `{x}`"),
+            DecodeError::InvalidPattern(x) => write!(f, "invalid pattern `{x}`"),
+            DecodeError::Impossible => write!(f, "no wiring is consistent with the patterns"),
+            DecodeError::Ambiguous => write!(f, "more than one wiring fits the patterns"),
         }
     }
-    Some(u8)
 }
 
-fn str_to_signals(input: &str) -> Vec<u8> {
-    input
-        .split_ascii_whitespace()
-        .flat_map(pattern_to_signal)
-        .collect()
+impl std::error::Error for DecodeError {}
+
+// `segments[i]` is the display segment driven by wire `i`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WireMapping {
+    names: Vec<char>,
+    segments: Vec<usize>,
 }
 
-fn new_signal_to_number_converter(mut signals: Vec<u8>) -> HashMap<u8, u8> {
-    const SEVEN_BITS: u8 = 0b111_1111;
+impl WireMapping {
+    pub fn segment(&self, wire: char) -> Option<char> {
+        let i = self.names.iter().position(|&x| x == wire)?;
+        Some(self.names[self.segments[i]])
+    }
 
-    let mut converter = HashMap::new();
-    debug_assert_eq!(signals.len(), 10);
+    pub fn pairs(&self) -> Vec<(char, char)> {
+        self.segments
+            .iter()
+            .enumerate()
+            .map(|(wire, &segment)| (self.names[wire], self.names[segment]))
+            .collect()
+    }
 
-    let pos_by_ones_count = |vec: &Vec<u8>, x| {
-        vec.iter()
-            .map(|c| c.count_ones())
-            .position(|c| c == x)
-            .unwrap()
-    };
+    fn map(&self, wires: u32) -> u32 {
+        self.segments
+            .iter()
+            .enumerate()
+            .filter(|(wire, _)| wires & (1 << wire) != 0)
+            .fold(0, |acc, (_, &segment)| acc | (1 << segment))
+    }
+}
 
-    let pos_by_and_match = |vec: &Vec<u8>, x: u8| vec.iter().position(|c| (c & x) == x).unwrap();
+impl fmt::Display for WireMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pairs: Vec<String> = self
+            .pairs()
+            .into_iter()
+            .map(|(wire, segment)| format!("{wire}->{segment}"))
+            .collect();
+        write!(f, "{}", pairs.join(" "))
+    }
+}
 
-    let pos_by_match = |vec: &Vec<u8>, x: u8| vec.iter().position(|c| *c == x).unwrap();
+fn parse_input_output(input: &str) -> Option<(&str, &str)> {
+    input
+        .split_once('|')
+        .map(|(inp, outp)| (inp.trim_end(), outp.trim_start()))
+}
 
-    let one = signals.swap_remove(pos_by_ones_count(&signals, 2));
-    converter.insert(one, 1);
+fn parse_values(input: &str) -> Vec<(&str, &str)> {
+    input
+        .lines()
+        .filter_map(|x| parse_input_output(x.trim_end()))
+        .collect()
+}
 
-    let seven = signals.swap_remove(pos_by_ones_count(&signals, 3));
-    converter.insert(seven, 7);
+// Searches wire-to-segment assignments, always extending the wire with the
+// fewest remaining candidates and pruning as soon as an observed pattern can
+// no longer match any digit of its size.
+struct Solver<'a> {
+    definition: &'a DisplayDefinition,
+    patterns: Vec<(u32, Vec<u32>)>,
+    domains: Vec<u32>,
+    segments: Vec<Option<usize>>,
+    solutions: Vec<WireMapping>,
+}
 
-    let four = signals.swap_remove(pos_by_ones_count(&signals, 4));
-    converter.insert(four, 4);
+impl<'a> Solver<'a> {
+    fn new(definition: &'a DisplayDefinition, patterns: &[u32]) -> Solver<'a> {
+        let all = definition.all();
+        let mut solver = Solver {
+            definition,
+            patterns: Vec::new(),
+            domains: vec![all; definition.segments.len()],
+            segments: vec![None; definition.segments.len()],
+            solutions: Vec::new(),
+        };
 
-    let eight = signals.swap_remove(pos_by_ones_count(&signals, 7));
-    converter.insert(eight, 8);
+        for &pattern in patterns.iter() {
+            if solver.patterns.iter().any(|(x, _)| *x == pattern) {
+                continue;
+            }
+            let candidates: Vec<u32> = definition
+                .symbols
+                .iter()
+                .map(|(_, x)| *x)
+                .filter(|x| x.count_ones() == pattern.count_ones())
+                .collect();
 
-    let nine = signals.swap_remove(pos_by_and_match(&signals, four | seven));
-    converter.insert(nine, 9);
+            let inside = candidates.iter().fold(0, |acc, x| acc | x);
+            let outside = candidates.iter().fold(0, |acc, x| acc | (!x & all));
+            for (wire, domain) in solver.domains.iter_mut().enumerate() {
+                *domain &= if pattern & (1 << wire) != 0 {
+                    inside
+                } else {
+                    outside
+                };
+            }
+            solver.patterns.push((pattern, candidates));
+        }
+        solver
+    }
 
-    let six = signals.swap_remove(pos_by_and_match(&signals, !one & SEVEN_BITS));
-    converter.insert(six, 6);
+    fn consistent(&self) -> bool {
+        self.patterns.iter().all(|(pattern, candidates)| {
+            let (mut lit, mut unlit) = (0, 0);
+            for (wire, segment) in self.segments.iter().enumerate() {
+                if let Some(segment) = segment {
+                    if pattern & (1 << wire) != 0 {
+                        lit |= 1 << segment;
+                    } else {
+                        unlit |= 1 << segment;
+                    }
+                }
+            }
+            candidates.iter().any(|x| lit & x == lit && unlit & x == 0)
+        })
+    }
 
-    let five = signals.swap_remove(pos_by_match(&signals, six & nine));
-    converter.insert(five, 5);
+    fn search(&mut self, used: u32) {
+        if self.solutions.len() > 1 {
+            return;
+        }
 
-    let zero = signals.swap_remove(pos_by_and_match(&signals, seven | (!four & SEVEN_BITS)));
-    converter.insert(zero, 0);
+        let next = (0..self.segments.len())
+            .filter(|&wire| self.segments[wire].is_none())
+            .min_by_key(|&wire| (self.domains[wire] & !used).count_ones());
+        let Some(wire) = next else {
+            self.solutions.push(WireMapping {
+                names: self.definition.segments.clone(),
+                segments: self.segments.iter().map(|x| x.unwrap()).collect(),
+            });
+            return;
+        };
 
-    let three = signals.swap_remove(pos_by_and_match(&signals, one | (!zero & SEVEN_BITS)));
-    converter.insert(three, 3);
+        let candidates = self.domains[wire] & !used;
+        for segment in 0..self.segments.len() {
+            if candidates & (1 << segment) == 0 {
+                continue;
+            }
+            self.segments[wire] = Some(segment);
+            if self.consistent() {
+                self.search(used | (1 << segment));
+            }
+            self.segments[wire] = None;
+        }
+    }
+}
 
-    let two = signals.pop().unwrap();
-    converter.insert(two, 2);
+// Any subset of the symbols' patterns may be given, output patterns included.
+pub fn solve_wiring(
+    definition: &DisplayDefinition,
+    patterns: &[&str],
+) -> Result<WireMapping, DecodeError> {
+    let signals = definition.signals(&patterns.join(" "))?;
+    let mut solver = Solver::new(definition, &signals);
+    solver.search(0);
+    match solver.solutions.len() {
+        0 => Err(DecodeError::Impossible),
+        1 => Ok(solver.solutions.pop().unwrap()),
+        _ => Err(DecodeError::Ambiguous),
+    }
+}
 
-    converter
+pub fn decode_line_with(definition: &DisplayDefinition, line: &str) -> Result<String, DecodeError> {
+    let (inp, outp) = parse_input_output(line.trim_end()).ok_or(DecodeError::MissingSeparator)?;
+    let patterns: Vec<&str> = inp
+        .split_ascii_whitespace()
+        .chain(outp.split_ascii_whitespace())
+        .collect();
+    let mapping = solve_wiring(definition, &patterns)?;
+
+    Ok(outp
+        .split_ascii_whitespace()
+        .map(|x| definition.decode(&mapping, x).unwrap())
+        .collect())
+}
+
+pub fn decode_line(line: &str) -> Result<usize, DecodeError> {
+    let digits = decode_line_with(&DisplayDefinition::seven_segment(), line)?;
+    Ok(digits
+        .chars()
+        .fold(0, |acc, x| (acc * 10) + x.to_digit(10).unwrap() as usize))
 }
 
 pub fn solve1(input: &str) -> usize {
@@ -99,15 +306,10 @@ pub fn solve1(input: &str) -> usize {
 }
 
 pub fn solve2(input: &str) -> usize {
-    parse_values(input)
-        .into_iter()
-        .map(|(inp, outp)| {
-            let number_converter = new_signal_to_number_converter(str_to_signals(inp));
-            str_to_signals(outp)
-                .into_iter()
-                .map(|x| *number_converter.get(&x).unwrap() as usize)
-                .fold(0, |acc, x| (acc * 10) + x)
-        })
+    input
+        .lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| decode_line(x).unwrap())
         .sum()
 }
 
@@ -126,4 +328,87 @@ mod tests {
         let input = include_str!("../test");
         assert_eq!(solve2(input), 61229);
     }
+
+    #[test]
+    fn test_solve_wiring() {
+        let line = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab";
+        let patterns: Vec<&str> = line.split_ascii_whitespace().collect();
+        let seven = DisplayDefinition::seven_segment();
+        let solve_wiring = |patterns: &[&str]| solve_wiring(&seven, patterns);
+        let mapping = solve_wiring(&patterns).unwrap();
+        assert_eq!(mapping.to_string(), "a->c b->f c->g d->a e->b f->d g->e");
+        assert_eq!(mapping.segment('d'), Some('a'));
+        assert_eq!(seven.decode(&mapping, "cdfeb"), Some('5'));
+
+        // 1, 7 and 4 alone leave several wires interchangeable, but adding
+        // 0, 6 and 9 pins every wire down.
+        assert_eq!(
+            solve_wiring(&["ab", "dab", "eafb"]),
+            Err(DecodeError::Ambiguous)
+        );
+        assert_eq!(
+            solve_wiring(&["ab", "dab", "eafb", "cagedb", "cdfgeb", "cefabd"]),
+            Ok(mapping)
+        );
+
+        assert_eq!(
+            solve_wiring(&["ab", "abc", "abcd", "abcde", "bcdef", "cdefg", "abcfg"]),
+            Err(DecodeError::Impossible)
+        );
+        assert_eq!(solve_wiring(&["abc", "abd"]), Err(DecodeError::Impossible));
+        assert_eq!(
+            solve_wiring(&["ab", "xyz"]),
+            Err(DecodeError::InvalidPattern("xyz".to_string()))
+        );
+        assert_eq!(
+            decode_line("ab aab | ab"),
+            Err(DecodeError::InvalidPattern("aab".to_string()))
+        );
+        assert_eq!(decode_line("ab cd"), Err(DecodeError::MissingSeparator));
+    }
+
+    #[test]
+    fn test_fourteen_segment() {
+        // a-f as on a seven-segment display, g/h the two middle halves, i-k
+        // the upper diagonal and vertical strokes and l-n the lower ones.
+        let symbols = [
+            ('A', "abcefgh"),
+            ('E', "adefg"),
+            ('F', "aefg"),
+            ('H', "bcefgh"),
+            ('I', "adjm"),
+            ('K', "efgkn"),
+            ('L', "def"),
+            ('M', "bcefik"),
+            ('N', "bcefin"),
+            ('P', "abefgh"),
+            ('T', "ajm"),
+            ('V', "efkl"),
+            ('X', "ikln"),
+            ('Y', "ikm"),
+            ('Z', "adkl"),
+            ('9', "abcdfgh"),
+        ];
+        let definition = DisplayDefinition::new("abcdefghijklmn", &symbols).unwrap();
+
+        let scramble = |pattern: &str| -> String {
+            pattern
+                .chars()
+                .map(|x| b"nmlkjihgfedcba"[(x as u8 - b'a') as usize] as char)
+                .collect()
+        };
+        let patterns: Vec<String> = symbols.iter().map(|(_, x)| scramble(x)).collect();
+        let output: Vec<String> = ["def", "bcefgh", "adjm", "efgkn"]
+            .into_iter()
+            .map(scramble)
+            .collect();
+        let line = format!("{} | {}", patterns.join(" "), output.join(" "));
+        assert_eq!(decode_line_with(&definition, &line), Ok("LHIK".to_string()));
+
+        assert_eq!(
+            DisplayDefinition::new("abca", &[]),
+            Err(DecodeError::InvalidDefinition("abca".to_string()))
+        );
+        assert!(DisplayDefinition::new("ab", &[('1', "a"), ('2', "a")]).is_err());
+    }
 }
@@ -1,126 +1,305 @@
-use std::collections::{BinaryHeap, HashSet};
+use std::collections::VecDeque;
+use std::fmt;
 
-fn parse_values(input: &str) -> Vec<Vec<u8>> {
-    input
-        .lines()
-        .map(|x| x.bytes().map(|x| x - b'0').collect())
-        .collect()
+const NO_BASIN: u32 = u32::MAX;
+
+// Heights stored row by row in a single allocation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HeightMap {
+    width: usize,
+    height: usize,
+    cells: Vec<u8>,
 }
 
-fn left(i: usize, j: usize) -> Option<(usize, usize)> {
-    j.checked_sub(1).map(|j| (i, j))
+impl HeightMap {
+    fn get(&self, i: usize, j: usize) -> u8 {
+        self.cells[i * self.width + j]
+    }
+
+    fn neighbours(&self, index: usize) -> impl Iterator<Item = usize> {
+        let (i, j) = (index / self.width, index % self.width);
+        let width = self.width;
+        [
+            j.checked_sub(1).map(|_| index - 1),
+            (j + 1 < self.width).then_some(index + 1),
+            i.checked_sub(1).map(|_| index - width),
+            (i + 1 < self.height).then_some(index + width),
+        ]
+        .into_iter()
+        .flatten()
+    }
 }
 
-fn right(values: &[Vec<u8>], i: usize, j: usize) -> Option<(usize, usize)> {
-    j.checked_add(1)
-        .filter(|j| *j < values[i].len())
-        .map(|j| (i, j))
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Basin {
+    pub low_point: (usize, usize),
+    pub size: usize,
 }
 
-fn up(i: usize, j: usize) -> Option<(usize, usize)> {
-    i.checked_sub(1).map(|i| (i, j))
+// `labels` holds the index into `basins` for every cell, or `None` for cells
+// outside every basin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasinMap {
+    pub width: usize,
+    pub height: usize,
+    labels: Vec<u32>,
+    pub basins: Vec<Basin>,
 }
 
-fn down(values: &[Vec<u8>], i: usize, j: usize) -> Option<(usize, usize)> {
-    i.checked_add(1)
-        .filter(|i| *i < values.len())
-        .map(|i| (i, j))
+impl BasinMap {
+    pub fn label(&self, i: usize, j: usize) -> Option<usize> {
+        let label = self.labels[i * self.width + j];
+        (label != NO_BASIN).then_some(label as usize)
+    }
+
+    // Basin ids ordered from the largest basin down.
+    pub fn largest(&self, n: usize) -> Vec<usize> {
+        let mut ids: Vec<usize> = (0..self.basins.len()).collect();
+        ids.sort_by_key(|&x| std::cmp::Reverse(self.basins[x].size));
+        ids.truncate(n);
+        ids
+    }
 }
 
-fn low_points(values: &Vec<Vec<u8>>) -> Vec<(usize, usize)> {
-    let mut vec = Vec::new();
-    for i in 0..values.len() {
-        for j in 0..values[i].len() {
-            let point = values[i][j];
-            if let Some((i, j)) = left(i, j) {
-                if values[i][j] <= point {
-                    continue;
-                }
-            }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapError {
+    InvalidCharacter {
+        line: usize,
+        column: usize,
+        found: char,
+    },
+    WidthMismatch {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+}
 
-            if let Some((i, j)) = right(values, i, j) {
-                if values[i][j] <= point {
-                    continue;
-                }
-            }
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::InvalidCharacter {
+                line,
+                column,
+                found,
+            } => write!(
+                f,
+                "line {line}, column {column}: expected a digit, found `{found}`"
+            ),
+            MapError::WidthMismatch {
+                line,
+                expected,
+                found,
+            } => write!(f, "line {line}: expected {expected} heights, found {found}"),
+        }
+    }
+}
 
-            if let Some((i, j)) = up(i, j) {
-                if values[i][j] <= point {
-                    continue;
-                }
-            }
+impl std::error::Error for MapError {}
 
-            if let Some((i, j)) = down(values, i, j) {
-                if values[i][j] <= point {
-                    continue;
-                }
-            }
+// Blank lines are skipped but still counted for error positions.
+fn parse_values(input: &str) -> Result<HeightMap, MapError> {
+    let mut width = None;
+    let mut height = 0;
+    let mut cells = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
 
-            vec.push((i, j));
+        let found = line.chars().count();
+        let expected = *width.get_or_insert(found);
+        if found != expected {
+            return Err(MapError::WidthMismatch {
+                line: i + 1,
+                expected,
+                found,
+            });
+        }
+        for (j, c) in line.chars().enumerate() {
+            let value = c.to_digit(10).ok_or(MapError::InvalidCharacter {
+                line: i + 1,
+                column: j + 1,
+                found: c,
+            })?;
+            cells.push(value as u8);
         }
+        height += 1;
     }
-    vec
+
+    Ok(HeightMap {
+        width: width.unwrap_or(0),
+        height,
+        cells,
+    })
 }
 
-fn basin_size(
-    values: &Vec<Vec<u8>>,
-    visited: &mut HashSet<(usize, usize)>,
-    i: usize,
-    j: usize,
-) -> usize {
-    let point = values[i][j];
-    if visited.contains(&(i, j)) || point == 9 {
-        return 0;
-    }
-    visited.insert((i, j));
+fn low_points(values: &HeightMap) -> Vec<(usize, usize)> {
+    (0..values.cells.len())
+        .filter(|&x| {
+            values
+                .neighbours(x)
+                .all(|y| values.cells[y] > values.cells[x])
+        })
+        .map(|x| (x / values.width, x % values.width))
+        .collect()
+}
 
-    let mut size = 1;
-    if let Some((i, j)) = left(i, j) {
-        if values[i][j] > point {
-            size += basin_size(values, visited, i, j);
+// Floods each basin breadth-first from its low point, climbing to strictly
+// higher cells below 9. A cell reachable from several low points stays with
+// the first basin that claims it.
+fn label_basins(values: &HeightMap) -> BasinMap {
+    let mut labels = vec![NO_BASIN; values.cells.len()];
+    let mut basins = Vec::new();
+    let mut queue = VecDeque::new();
+
+    for (i, j) in low_points(values) {
+        let start = i * values.width + j;
+        if labels[start] != NO_BASIN || values.cells[start] == 9 {
+            continue;
         }
-    }
 
-    if let Some((i, j)) = right(values, i, j) {
-        if values[i][j] > point {
-            size += basin_size(values, visited, i, j);
+        let id = basins.len() as u32;
+        labels[start] = id;
+        queue.push_back(start);
+        let mut size = 0;
+        while let Some(x) = queue.pop_front() {
+            size += 1;
+            for y in values.neighbours(x) {
+                if labels[y] == NO_BASIN
+                    && values.cells[y] != 9
+                    && values.cells[y] > values.cells[x]
+                {
+                    labels[y] = id;
+                    queue.push_back(y);
+                }
+            }
         }
+        basins.push(Basin {
+            low_point: (i, j),
+            size,
+        });
     }
 
-    if let Some((i, j)) = up(i, j) {
-        if values[i][j] > point {
-            size += basin_size(values, visited, i, j);
-        }
+    BasinMap {
+        width: values.width,
+        height: values.height,
+        labels,
+        basins,
     }
+}
+
+pub fn basins(input: &str) -> Result<BasinMap, MapError> {
+    let values = parse_values(input)?;
+    Ok(label_basins(&values))
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+const ANSI_LOW_POINT: &str = "\x1b[1;31m";
+const ANSI_RIDGE: &str = "\x1b[2m";
+const ANSI_LARGEST: [&str; 3] = ["\x1b[30;43m", "\x1b[30;46m", "\x1b[30;45m"];
 
-    if let Some((i, j)) = down(values, i, j) {
-        if values[i][j] > point {
-            size += basin_size(values, visited, i, j);
+// Plain output keeps heights only inside the three largest basins, marks low
+// points with `*` and everything else with `.`. ANSI output keeps every
+// height and highlights with colour instead.
+pub fn render(input: &str, ansi: bool) -> Result<String, MapError> {
+    let values = parse_values(input)?;
+    let map = label_basins(&values);
+    let largest = map.largest(3);
+    let low: Vec<usize> = low_points(&values)
+        .into_iter()
+        .map(|(i, j)| i * values.width + j)
+        .collect();
+
+    let mut out = String::new();
+    for i in 0..values.height {
+        for j in 0..values.width {
+            let height = (b'0' + values.get(i, j)) as char;
+            let is_low = low.binary_search(&(i * values.width + j)).is_ok();
+            let rank = map
+                .label(i, j)
+                .and_then(|x| largest.iter().position(|&y| y == x));
+
+            if !ansi {
+                out.push(match (is_low, rank) {
+                    (true, _) => '*',
+                    (false, Some(_)) => height,
+                    (false, None) => '.',
+                });
+                continue;
+            }
+
+            let style = match (is_low, rank) {
+                (true, _) => ANSI_LOW_POINT,
+                (false, Some(rank)) => ANSI_LARGEST[rank],
+                (false, None) if values.get(i, j) == 9 => ANSI_RIDGE,
+                (false, None) => "",
+            };
+            if style.is_empty() {
+                out.push(height);
+            } else {
+                out.push_str(style);
+                out.push(height);
+                out.push_str(ANSI_RESET);
+            }
         }
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+// Spreads hues by the golden ratio so neighbouring ids get distinct colours.
+fn basin_colour(id: usize) -> [u8; 3] {
+    let hue = (id as f64 * 0.618_033_988_75).fract() * 6.0;
+    let (saturation, value) = (0.65, 0.95);
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    [r, g, b].map(|c| ((c + m) * 255.0).round() as u8)
+}
+
+// Binary PPM with a colour per basin, black outside basins and white low
+// points.
+pub fn basin_ppm(input: &str) -> Result<Vec<u8>, MapError> {
+    let values = parse_values(input)?;
+    let map = label_basins(&values);
+    let mut pixels: Vec<[u8; 3]> = (0..values.cells.len())
+        .map(|x| match map.labels[x] {
+            NO_BASIN => [0, 0, 0],
+            id => basin_colour(id as usize),
+        })
+        .collect();
+    for (i, j) in low_points(&values) {
+        pixels[i * values.width + j] = [255, 255, 255];
     }
-    size
+
+    let mut ppm = format!("P6\n{} {}\n255\n", values.width, values.height).into_bytes();
+    ppm.extend(pixels.into_iter().flatten());
+    Ok(ppm)
 }
 
 pub fn solve1(input: &str) -> usize {
-    let values = parse_values(input);
+    let values = parse_values(input).unwrap();
     low_points(&values)
         .into_iter()
-        .map(|x| values[x.0][x.1] as usize + 1)
+        .map(|(i, j)| values.get(i, j) as usize + 1)
         .sum()
 }
 
 pub fn solve2(input: &str) -> usize {
-    let values = parse_values(input);
-    let mut visited: HashSet<(usize, usize)> = HashSet::new();
-    let mut basin_sizes: BinaryHeap<usize> = low_points(&values)
+    let map = basins(input).unwrap();
+    map.largest(3)
         .into_iter()
-        .map(|(i, j)| basin_size(&values, &mut visited, i, j))
-        .collect();
-    let mut product = 1;
-    for _ in 0..3 {
-        product *= basin_sizes.pop().unwrap();
-    }
-    product
+        .map(|x| map.basins[x].size)
+        .product()
 }
 
 #[cfg(test)]
@@ -138,4 +317,83 @@ mod tests {
         let input = include_str!("../test");
         assert_eq!(solve2(input), 1134);
     }
+
+    #[test]
+    fn test_basins() {
+        let input = include_str!("../test");
+        let map = basins(input).unwrap();
+        let basins: Vec<_> = map.basins.iter().map(|x| (x.low_point, x.size)).collect();
+        assert_eq!(
+            basins,
+            [((0, 1), 3), ((0, 9), 9), ((2, 2), 14), ((4, 6), 9)]
+        );
+        assert_eq!(map.label(0, 0), Some(0));
+        assert_eq!(map.label(0, 2), None);
+        assert_eq!(map.label(4, 9), Some(3));
+        assert_eq!(map.largest(2), [2, 1]);
+    }
+
+    #[test]
+    fn test_render() {
+        let input = include_str!("../test");
+        assert_eq!(
+            render(input, false).unwrap(),
+            ".*...4321*\n\
+             ..878.4.21\n\
+             .8*678.8.2\n\
+             87678.678.\n\
+             .8...6*678\n"
+        );
+        let ansi = render(input, true).unwrap();
+        assert!(ansi.starts_with(&format!("2{ANSI_LOW_POINT}1{ANSI_RESET}")));
+        assert_eq!(ansi.matches(ANSI_LOW_POINT).count(), 4);
+
+        let ppm = basin_ppm(input).unwrap();
+        let header = "P6\n10 5\n255\n";
+        assert!(ppm.starts_with(header.as_bytes()));
+        assert_eq!(ppm.len(), header.len() + 3 * 50);
+        let pixel = |i: usize, j: usize| &ppm[header.len() + 3 * (i * 10 + j)..][..3];
+        assert_eq!(pixel(0, 1), [255, 255, 255]);
+        assert_eq!(pixel(0, 2), [0, 0, 0]);
+        assert_eq!(pixel(0, 0), basin_colour(0));
+        assert_ne!(basin_colour(0), basin_colour(1));
+    }
+
+    #[test]
+    fn test_large_map() {
+        let (width, height) = (2000, 1000);
+        let mut input = String::with_capacity((width + 1) * height);
+        for i in 0..height {
+            for j in 0..width {
+                input.push((b'0' + ((i + j) % 9) as u8) as char);
+            }
+            input.push('\n');
+        }
+        let map = basins(&input).unwrap();
+        assert_eq!(
+            map.basins.iter().map(|x| x.size).sum::<usize>(),
+            width * height
+        );
+        assert!(map.label(height - 1, width - 1).is_some());
+    }
+
+    #[test]
+    fn test_invalid_map() {
+        assert_eq!(
+            basins("123\n\n12\n").unwrap_err(),
+            MapError::WidthMismatch {
+                line: 3,
+                expected: 3,
+                found: 2
+            }
+        );
+        assert_eq!(
+            render("123\n1a3\n", false).unwrap_err(),
+            MapError::InvalidCharacter {
+                line: 2,
+                column: 2,
+                found: 'a'
+            }
+        );
+    }
 }
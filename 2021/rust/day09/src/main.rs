@@ -1,5 +1,21 @@
+use std::env;
+use std::fs;
+
 fn main() {
     let input = include_str!("../input");
     println!("{}", day09::solve1(input));
     println!("{}", day09::solve2(input));
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ascii" => print!("{}", day09::render(input, false).unwrap()),
+            "--ansi" => print!("{}", day09::render(input, true).unwrap()),
+            "--ppm" => {
+                let path = args.next().expect("missing output path");
+                fs::write(path, day09::basin_ppm(input).unwrap()).unwrap();
+            }
+            _ => panic!("unknown option `{arg}`, expected --ascii, --ansi or --ppm"),
+        }
+    }
 }
@@ -1,100 +1,252 @@
-fn parse_values(input: &str) -> Vec<&str> {
-    input.lines().collect()
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BracketPair {
+    pub open: char,
+    pub close: char,
+    pub corruption_score: usize,
+    pub completion_score: usize,
 }
 
-pub fn solve1(input: &str) -> usize {
-    let values = parse_values(input);
-    let mut score = 0;
-    let mut characters: Vec<u8> = Vec::new();
-    for s in values.into_iter() {
-        for c in s.bytes() {
-            match c {
-                b')' => {
-                    if !matches!(characters.pop(), Some(b'(')) {
-                        score += 3;
-                        break;
-                    }
-                }
-                b']' => {
-                    if !matches!(characters.pop(), Some(b'[')) {
-                        score += 57;
-                        break;
-                    }
-                }
-                b'}' => {
-                    if !matches!(characters.pop(), Some(b'{')) {
-                        score += 1197;
-                        break;
-                    }
-                }
-                b'>' => {
-                    if !matches!(characters.pop(), Some(b'<')) {
-                        score += 25137;
-                        break;
-                    }
-                }
-                v => characters.push(v),
-            };
+// Any single characters can act as delimiters; characters belonging to no
+// pair are skipped by the checker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BracketSyntax {
+    pub pairs: Vec<BracketPair>,
+    pub multiplier: usize,
+}
+
+impl Default for BracketSyntax {
+    fn default() -> BracketSyntax {
+        let pair = |open, close, corruption_score, completion_score| BracketPair {
+            open,
+            close,
+            corruption_score,
+            completion_score,
+        };
+        BracketSyntax {
+            pairs: vec![
+                pair('(', ')', 3, 1),
+                pair('[', ']', 57, 2),
+                pair('{', '}', 1197, 3),
+                pair('<', '>', 25137, 4),
+            ],
+            multiplier: 5,
         }
-        characters.clear();
     }
-    score
 }
 
-pub fn solve2(input: &str) -> usize {
-    let values = parse_values(input);
-    let mut scores: Vec<usize> = Vec::new();
-    let mut characters: Vec<u8> = Vec::new();
-    'outer: for s in values.into_iter() {
-        for c in s.bytes() {
-            match c {
-                b')' => {
-                    if !matches!(characters.pop(), Some(b'(')) {
-                        characters.clear();
-                        continue 'outer;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigErrorKind {
+    InvalidEntry(String),
+    DuplicateCharacter(char),
+    MissingMultiplier,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub line: usize,
+    pub kind: ConfigErrorKind,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            ConfigErrorKind::InvalidEntry(x) => write!(f, "invalid entry `{x}`"),
+            ConfigErrorKind::DuplicateCharacter(x) => write!(f, "`{x}` is used more than once"),
+            ConfigErrorKind::MissingMultiplier => write!(f, "no multiplier given"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn single_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    let c = chars.next()?;
+    chars.next().is_none().then_some(c)
+}
+
+impl BracketSyntax {
+    // One `open close corruption_score completion_score` entry per line plus
+    // a `multiplier N` line; `#` starts a comment.
+    pub fn from_config(config: &str) -> Result<BracketSyntax, ConfigError> {
+        let mut pairs: Vec<BracketPair> = Vec::new();
+        let mut multiplier = None;
+        for (i, line) in config.lines().enumerate() {
+            let error = |kind| ConfigError { line: i + 1, kind };
+            let code = line.split('#').next().unwrap().trim();
+            let tokens: Vec<&str> = code.split_ascii_whitespace().collect();
+            let invalid = || error(ConfigErrorKind::InvalidEntry(code.to_string()));
+            match tokens[..] {
+                [] => {}
+                ["multiplier", n] => multiplier = Some(n.parse().map_err(|_| invalid())?),
+                [open, close, corruption_score, completion_score] => {
+                    let pair = BracketPair {
+                        open: single_char(open).ok_or_else(invalid)?,
+                        close: single_char(close).ok_or_else(invalid)?,
+                        corruption_score: corruption_score.parse().map_err(|_| invalid())?,
+                        completion_score: completion_score.parse().map_err(|_| invalid())?,
+                    };
+                    let used = |c: char| pairs.iter().any(|x| x.open == c || x.close == c);
+                    for c in [pair.open, pair.close] {
+                        if used(c) || pair.open == pair.close {
+                            return Err(error(ConfigErrorKind::DuplicateCharacter(c)));
+                        }
                     }
+                    pairs.push(pair);
                 }
-                b']' => {
-                    if !matches!(characters.pop(), Some(b'[')) {
-                        characters.clear();
-                        continue 'outer;
-                    }
+                _ => return Err(invalid()),
+            }
+        }
+
+        let multiplier = multiplier.ok_or(ConfigError {
+            line: config.lines().count(),
+            kind: ConfigErrorKind::MissingMultiplier,
+        })?;
+        Ok(BracketSyntax { pairs, multiplier })
+    }
+
+    fn opening(&self, c: char) -> Option<usize> {
+        self.pairs.iter().position(|x| x.open == c)
+    }
+
+    fn closing(&self, c: char) -> Option<usize> {
+        self.pairs.iter().position(|x| x.close == c)
+    }
+
+    pub fn status(&self, line: &str) -> LineStatus {
+        let mut open: Vec<usize> = Vec::new();
+        for (i, c) in line.chars().enumerate() {
+            if let Some(pair) = self.opening(c) {
+                open.push(pair);
+            } else if self.closing(c).is_some() {
+                let last = open.pop();
+                if last.map(|x| self.pairs[x].close) != Some(c) {
+                    return LineStatus::Corrupted {
+                        column: i + 1,
+                        expected: last.map(|x| self.pairs[x].close),
+                        found: c,
+                    };
                 }
-                b'}' => {
-                    if !matches!(characters.pop(), Some(b'{')) {
-                        characters.clear();
-                        continue 'outer;
-                    }
+            }
+        }
+
+        if open.is_empty() {
+            LineStatus::Valid
+        } else {
+            LineStatus::Incomplete {
+                completion: open
+                    .into_iter()
+                    .rev()
+                    .map(|x| self.pairs[x].close)
+                    .collect(),
+            }
+        }
+    }
+
+    pub fn diagnose(&self, input: &str) -> Vec<LineStatus> {
+        parse_values(input)
+            .into_iter()
+            .map(|x| self.status(x))
+            .collect()
+    }
+
+    pub fn corruption_score(&self, input: &str) -> usize {
+        self.diagnose(input)
+            .into_iter()
+            .map(|x| match x {
+                LineStatus::Corrupted { found, .. } => {
+                    self.pairs[self.closing(found).unwrap()].corruption_score
                 }
-                b'>' => {
-                    if !matches!(characters.pop(), Some(b'<')) {
-                        characters.clear();
-                        continue 'outer;
-                    }
+                _ => 0,
+            })
+            .sum()
+    }
+
+    // Median completion score over the incomplete lines, if any.
+    pub fn completion_score(&self, input: &str) -> Option<usize> {
+        let mut scores: Vec<usize> = self
+            .diagnose(input)
+            .into_iter()
+            .filter_map(|x| match x {
+                LineStatus::Incomplete { completion } => {
+                    Some(completion.chars().fold(0, |acc, x| {
+                        acc * self.multiplier
+                            + self.pairs[self.closing(x).unwrap()].completion_score
+                    }))
                 }
-                v => characters.push(v),
-            };
+                _ => None,
+            })
+            .collect();
+        if scores.is_empty() {
+            return None;
         }
 
-        let mut score = 0;
-        while let Some(v) = characters.pop() {
-            score *= 5;
-            score += match v {
-                b'(' => 1,
-                b'[' => 2,
-                b'{' => 3,
-                b'<' => 4,
-                _ => 0,
+        let mid = scores.len() / 2;
+        let (_, middle, _) = scores.select_nth_unstable(mid);
+        Some(*middle)
+    }
+
+    // Every line followed by a caret under its first error, or by the
+    // repaired line with carets under the completion.
+    pub fn annotate(&self, input: &str) -> String {
+        let mut out = String::new();
+        for line in parse_values(input) {
+            match self.status(line) {
+                LineStatus::Valid => out += &format!("{line}\n"),
+                LineStatus::Corrupted {
+                    column,
+                    expected,
+                    found,
+                } => {
+                    let expected = match expected {
+                        Some(x) => format!("`{x}`"),
+                        None => "nothing".to_string(),
+                    };
+                    out += &format!(
+                        "{line}\n{}^ expected {expected}, found `{found}`\n",
+                        " ".repeat(column - 1)
+                    );
+                }
+                LineStatus::Incomplete { completion } => {
+                    out += &format!(
+                        "{line}{completion}\n{}{} completed\n",
+                        " ".repeat(line.chars().count()),
+                        "^".repeat(completion.chars().count())
+                    );
+                }
             }
         }
-        scores.push(score);
-
-        characters.clear();
+        out
     }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineStatus {
+    // `column` is one-based; `expected` is `None` when nothing was open.
+    Corrupted {
+        column: usize,
+        expected: Option<char>,
+        found: char,
+    },
+    Incomplete {
+        completion: String,
+    },
+    Valid,
+}
+
+fn parse_values(input: &str) -> Vec<&str> {
+    input.lines().collect()
+}
+
+pub fn solve1(input: &str) -> usize {
+    BracketSyntax::default().corruption_score(input)
+}
 
-    let mid = scores.len() / 2;
-    let (_, middle, _) = scores.select_nth_unstable(mid);
-    *middle
+pub fn solve2(input: &str) -> usize {
+    BracketSyntax::default().completion_score(input).unwrap()
 }
 
 #[cfg(test)]
@@ -112,4 +264,73 @@ mod tests {
         let input = include_str!("../test");
         assert_eq!(solve2(input), 288957);
     }
+
+    #[test]
+    fn test_config() {
+        let config = "# chunks\n( ) 3 1\n[ ] 57 2\n{ } 1197 3\n< > 25137 4\nmultiplier 5\n";
+        assert_eq!(
+            BracketSyntax::from_config(config),
+            Ok(BracketSyntax::default())
+        );
+
+        // One-character tags: `a` opens and `A` closes, text in between is
+        // ignored.
+        let tags = BracketSyntax::from_config("a A 10 1\nb B 20 2\nmultiplier 3").unwrap();
+        let input = "a hello b world B A\na b A B\na b\n";
+        assert_eq!(tags.corruption_score(input), 10);
+        assert_eq!(tags.completion_score(input), Some(2 * 3 + 1));
+
+        assert_eq!(
+            BracketSyntax::from_config("( ) 3 1\n[ ( 1 1\n"),
+            Err(ConfigError {
+                line: 2,
+                kind: ConfigErrorKind::DuplicateCharacter('(')
+            })
+        );
+        assert_eq!(
+            BracketSyntax::from_config("( ) x 1\n").unwrap_err().kind,
+            ConfigErrorKind::InvalidEntry("( ) x 1".to_string())
+        );
+        assert_eq!(
+            BracketSyntax::from_config("( ) 3 1\n").unwrap_err().kind,
+            ConfigErrorKind::MissingMultiplier
+        );
+    }
+
+    #[test]
+    fn test_status() {
+        let syntax = BracketSyntax::default();
+        assert_eq!(
+            syntax.status("{([(<{}[<>[]}>{[]{[(<()>"),
+            LineStatus::Corrupted {
+                column: 13,
+                expected: Some(']'),
+                found: '}'
+            }
+        );
+        assert_eq!(
+            syntax.status("[({(<(())[]>[[{[]{<()<>>"),
+            LineStatus::Incomplete {
+                completion: "}}]])})]".to_string()
+            }
+        );
+        assert_eq!(
+            syntax.status("<>)"),
+            LineStatus::Corrupted {
+                column: 3,
+                expected: None,
+                found: ')'
+            }
+        );
+        assert_eq!(syntax.status("[<>({}){}[([])<>]]"), LineStatus::Valid);
+    }
+
+    #[test]
+    fn test_annotate() {
+        let syntax = BracketSyntax::default();
+        assert_eq!(
+            syntax.annotate("(]\n[<\n()\n"),
+            "(]\n ^ expected `)`, found `]`\n[<>]\n  ^^ completed\n()\n"
+        );
+    }
 }
@@ -1,5 +1,14 @@
+use std::env;
+
 fn main() {
     let input = include_str!("../input");
     println!("{}", day10::solve1(input));
     println!("{}", day10::solve2(input));
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--annotate" => print!("{}", day10::BracketSyntax::default().annotate(input)),
+            _ => panic!("unknown option `{arg}`, expected --annotate"),
+        }
+    }
 }
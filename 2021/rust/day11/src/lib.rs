@@ -1,118 +1,351 @@
-fn parse_values(input: &str) -> Vec<Vec<u8>> {
-    input
-        .lines()
-        .map(|x| x.bytes().map(|x| x - b'0').collect())
-        .collect()
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GridError {
+    Empty,
+    InvalidCharacter {
+        line: usize,
+        column: usize,
+        found: char,
+    },
+    WidthMismatch {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    AboveThreshold {
+        line: usize,
+        column: usize,
+        energy: u8,
+        threshold: u8,
+    },
 }
 
-fn left(i: usize, j: usize) -> Option<(usize, usize)> {
-    j.checked_sub(1).map(|j| (i, j))
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GridError::Empty => write!(f, "grid is empty"),
+            GridError::InvalidCharacter {
+                line,
+                column,
+                found,
+            } => write!(
+                f,
+                "line {line}, column {column}: expected a digit, found `{found}`"
+            ),
+            GridError::WidthMismatch {
+                line,
+                expected,
+                found,
+            } => write!(f, "line {line}: expected {expected} cells, found {found}"),
+            GridError::AboveThreshold {
+                line,
+                column,
+                energy,
+                threshold,
+            } => write!(
+                f,
+                "line {line}, column {column}: energy {energy} is above the threshold {threshold}"
+            ),
+        }
+    }
 }
 
-fn right(values: &[Vec<u8>], i: usize, j: usize) -> Option<(usize, usize)> {
-    j.checked_add(1)
-        .filter(|j| *j < values[i].len())
-        .map(|j| (i, j))
+impl std::error::Error for GridError {}
+
+// Default step bound for searches that may not end on their own.
+pub const STEP_LIMIT: usize = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighbourhood {
+    // The 4 orthogonal neighbours.
+    VonNeumann,
+    // The 8 orthogonal and diagonal neighbours.
+    Moore,
 }
 
-fn up(i: usize, j: usize) -> Option<(usize, usize)> {
-    i.checked_sub(1).map(|i| (i, j))
+impl Neighbourhood {
+    fn offsets(self) -> &'static [(isize, isize)] {
+        match self {
+            Neighbourhood::VonNeumann => &[(-1, 0), (0, -1), (0, 1), (1, 0)],
+            Neighbourhood::Moore => &[
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ],
+        }
+    }
 }
 
-fn down(values: &[Vec<u8>], i: usize, j: usize) -> Option<(usize, usize)> {
-    i.checked_add(1)
-        .filter(|i| *i < values.len())
-        .map(|i| (i, j))
+// A cell flashes once its energy goes above `threshold`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rules {
+    pub threshold: u8,
+    pub neighbourhood: Neighbourhood,
 }
 
-fn flash(values: &mut [Vec<u8>], flashes: &mut [Vec<bool>], i: usize, j: usize) {
-    if flashes[i][j] {
-        return;
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules {
+            threshold: 9,
+            neighbourhood: Neighbourhood::Moore,
+        }
     }
+}
 
-    if values[i][j] != 9 {
-        values[i][j] += 1;
-        return;
-    }
+// Steps are absolute, counted like `Simulation::steps`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: usize,
+    pub length: usize,
+}
 
-    values[i][j] = 0;
-    flashes[i][j] = true;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Synchronisation {
+    At(usize),
+    Never(Cycle),
+    // The step limit ran out before either happened.
+    Unknown,
+}
 
-    if let Some((i, j)) = up(i, j) {
-        if let Some((i, j)) = left(i, j) {
-            flash(values, flashes, i, j);
-        }
+// How a search for a repeated state ended.
+enum Search {
+    Cycle(Cycle),
+    Stopped,
+    LimitReached,
+}
 
-        if let Some((i, j)) = right(values, i, j) {
-            flash(values, flashes, i, j);
-        }
+// Takes the grid shape by value so the iterator doesn't borrow the grid.
+fn neighbours(
+    width: usize,
+    height: usize,
+    neighbourhood: Neighbourhood,
+    cell: usize,
+) -> impl Iterator<Item = usize> {
+    let (row, column) = (cell / width, cell % width);
+    neighbourhood.offsets().iter().filter_map(move |(di, dj)| {
+        let i = row.checked_add_signed(*di).filter(|i| *i < height)?;
+        let j = column.checked_add_signed(*dj).filter(|j| *j < width)?;
+        Some(i * width + j)
+    })
+}
 
-        flash(values, flashes, i, j);
-    }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Simulation {
+    width: usize,
+    height: usize,
+    energy: Vec<u16>,
+    rules: Rules,
+    steps: usize,
+}
 
-    if let Some((i, j)) = down(values, i, j) {
-        if let Some((i, j)) = left(i, j) {
-            flash(values, flashes, i, j);
-        }
+impl Simulation {
+    pub fn new(input: &str, rules: Rules) -> Result<Simulation, GridError> {
+        let mut width = None;
+        let mut energy = Vec::new();
+        for (i, line) in input.lines().enumerate() {
+            let found = line.chars().count();
+            let expected = *width.get_or_insert(found);
+            if found != expected {
+                return Err(GridError::WidthMismatch {
+                    line: i + 1,
+                    expected,
+                    found,
+                });
+            }
 
-        if let Some((i, j)) = right(values, i, j) {
-            flash(values, flashes, i, j);
+            for (j, c) in line.chars().enumerate() {
+                let value = c.to_digit(10).ok_or(GridError::InvalidCharacter {
+                    line: i + 1,
+                    column: j + 1,
+                    found: c,
+                })? as u8;
+                if value > rules.threshold {
+                    return Err(GridError::AboveThreshold {
+                        line: i + 1,
+                        column: j + 1,
+                        energy: value,
+                        threshold: rules.threshold,
+                    });
+                }
+                energy.push(u16::from(value));
+            }
         }
 
-        flash(values, flashes, i, j);
+        let width = width.filter(|x| *x > 0).ok_or(GridError::Empty)?;
+        Ok(Simulation {
+            width,
+            height: energy.len() / width,
+            energy,
+            rules,
+            steps: 0,
+        })
     }
 
-    if let Some((i, j)) = left(i, j) {
-        flash(values, flashes, i, j);
+    pub fn width(&self) -> usize {
+        self.width
     }
 
-    if let Some((i, j)) = right(values, i, j) {
-        flash(values, flashes, i, j);
+    pub fn height(&self) -> usize {
+        self.height
     }
-}
 
-fn step(values: &mut [Vec<u8>], flashes: &mut [Vec<bool>]) {
-    for i in 0..values.len() {
-        for j in 0..values[i].len() {
-            flash(values, flashes, i, j);
-        }
+    pub fn steps(&self) -> usize {
+        self.steps
     }
-}
 
-pub fn solve1(input: &str) -> usize {
-    let mut values = parse_values(input);
-    let mut flashes = vec![vec![false; values[0].len()]; values.len()];
-    let mut flash_count = 0;
-    for _ in 0..100 {
-        step(&mut values, &mut flashes);
-
-        flash_count += flashes.iter().flatten().filter(|x| **x).count();
+    pub fn energy(&self, row: usize, column: usize) -> u16 {
+        self.energy[row * self.width + column]
+    }
 
-        for line in flashes.iter_mut() {
-            line.fill(false);
+    // Raises a cell's energy and queues it the moment it crosses the
+    // threshold; cells above it have already flashed this step.
+    fn charge(&mut self, cell: usize, queue: &mut Vec<usize>) {
+        let threshold = u16::from(self.rules.threshold);
+        if self.energy[cell] <= threshold {
+            self.energy[cell] += 1;
+            if self.energy[cell] > threshold {
+                queue.push(cell);
+            }
         }
     }
 
-    flash_count
-}
+    // Advances one step and returns the `(row, column)` of every cell that
+    // flashed, in the order they flashed.
+    pub fn step(&mut self) -> Vec<(usize, usize)> {
+        let mut flashed = Vec::new();
+        for cell in 0..self.energy.len() {
+            self.charge(cell, &mut flashed);
+        }
 
-pub fn solve2(input: &str) -> usize {
-    let mut values = parse_values(input);
-    let mut flashes = vec![vec![false; values[0].len()]; values.len()];
+        let mut next = 0;
+        while let Some(&cell) = flashed.get(next) {
+            for neighbour in neighbours(self.width, self.height, self.rules.neighbourhood, cell) {
+                self.charge(neighbour, &mut flashed);
+            }
+            next += 1;
+        }
+
+        for cell in flashed.iter() {
+            self.energy[*cell] = 0;
+        }
+        self.steps += 1;
+
+        flashed
+            .into_iter()
+            .map(|x| (x / self.width, x % self.width))
+            .collect()
+    }
 
-    let mut i = 1;
-    loop {
-        step(&mut values, &mut flashes);
+    // Brent's cycle detection: a hare steps through every state in order
+    // while a tortoise waits at power-of-two distances behind it, so only two
+    // grids are kept however long the search runs. `visit` is given the
+    // number of cells flashed on every hare step and can stop the search.
+    // When a cycle is found the hare has seen every reachable state.
+    fn find_cycle(&self, limit: usize, mut visit: impl FnMut(usize) -> bool) -> Search {
+        let mut tortoise = self.clone();
+        let mut hare = self.clone();
+        let (mut power, mut length) = (1, 0);
+        for _ in 0..limit {
+            if visit(hare.step().len()) {
+                return Search::Stopped;
+            }
+            length += 1;
+            if hare.energy == tortoise.energy {
+                let mut tortoise = self.clone();
+                let mut hare = self.clone();
+                for _ in 0..length {
+                    hare.step();
+                }
+                let mut start = 0;
+                while tortoise.energy != hare.energy {
+                    tortoise.step();
+                    hare.step();
+                    start += 1;
+                }
+                return Search::Cycle(Cycle {
+                    start: self.steps + start,
+                    length,
+                });
+            }
+            if length == power {
+                tortoise.clone_from(&hare);
+                power *= 2;
+                length = 0;
+            }
+        }
+        Search::LimitReached
+    }
 
-        if flashes.iter().flatten().all(|x| *x) {
-            return i;
+    // The first step from here on where every cell flashes at once.
+    pub fn synchronisation(&self, limit: usize) -> Synchronisation {
+        let cells = self.energy.len();
+        let mut step = self.steps;
+        let search = self.find_cycle(limit, |flashed| {
+            step += 1;
+            flashed == cells
+        });
+        match search {
+            Search::Stopped => Synchronisation::At(step),
+            Search::Cycle(cycle) => Synchronisation::Never(cycle),
+            Search::LimitReached => Synchronisation::Unknown,
         }
+    }
 
-        for line in flashes.iter_mut() {
-            line.fill(false);
+    // Total flashes over the next `steps` steps, extrapolated from the
+    // cycle once one is found; `None` if `limit` runs out first or the total
+    // doesn't fit in a u64.
+    pub fn flashes_after(&self, steps: u64, limit: usize) -> Option<u64> {
+        if steps == 0 {
+            return Some(0);
         }
 
-        i += 1;
+        let (mut total, mut taken) = (0, 0);
+        let search = self.find_cycle(limit, |flashed| {
+            total += flashed as u64;
+            taken += 1;
+            taken == steps
+        });
+        let cycle = match search {
+            Search::Stopped => return Some(total),
+            Search::Cycle(cycle) => cycle,
+            Search::LimitReached => return None,
+        };
+
+        let mut simulation = self.clone();
+        let mut count =
+            |steps: u64| -> u64 { (0..steps).map(|_| simulation.step().len() as u64).sum() };
+        let (start, length) = ((cycle.start - self.steps) as u64, cycle.length as u64);
+        let before = count(start);
+        let per_cycle = count(length);
+        let remaining = steps - start;
+        let rest = count(remaining % length);
+        per_cycle
+            .checked_mul(remaining / length)?
+            .checked_add(before)?
+            .checked_add(rest)
+    }
+}
+
+pub fn solve1(input: &str) -> usize {
+    let mut simulation = Simulation::new(input, Rules::default()).unwrap();
+    (0..100).map(|_| simulation.step().len()).sum()
+}
+
+pub fn solve2(input: &str) -> usize {
+    let simulation = Simulation::new(input, Rules::default()).unwrap();
+    match simulation.synchronisation(STEP_LIMIT) {
+        Synchronisation::At(step) => step,
+        Synchronisation::Never(cycle) => panic!(
+            "never synchronises: cycle of length {} from step {}",
+            cycle.length, cycle.start
+        ),
+        Synchronisation::Unknown => panic!("no synchronisation within {STEP_LIMIT} steps"),
     }
 }
 
@@ -120,6 +353,19 @@ pub fn solve2(input: &str) -> usize {
 mod tests {
     use super::*;
 
+    fn grid(simulation: &Simulation) -> String {
+        (0..simulation.height())
+            .map(|i| {
+                (0..simulation.width())
+                    .map(|j| simulation.energy(i, j).to_string())
+                    .collect::<String>()
+                    + "\n"
+            })
+            .collect()
+    }
+
+    const SMALL: &str = "11111\n19991\n19191\n19991\n11111\n";
+
     #[test]
     fn test_solve1() {
         let input = include_str!("../test");
@@ -131,4 +377,129 @@ mod tests {
         let input = include_str!("../test");
         assert_eq!(solve2(input), 195);
     }
+
+    #[test]
+    fn test_rules() {
+        let mut moore = Simulation::new(SMALL, Rules::default()).unwrap();
+        assert_eq!(moore.step().len(), 9);
+        assert_eq!(grid(&moore), "34543\n40004\n50005\n40004\n34543\n");
+
+        let mut von_neumann = Simulation::new(
+            SMALL,
+            Rules {
+                threshold: 9,
+                neighbourhood: Neighbourhood::VonNeumann,
+            },
+        )
+        .unwrap();
+        assert_eq!(von_neumann.step().len(), 8);
+        assert_eq!(grid(&von_neumann), "23332\n30003\n30603\n30003\n23332\n");
+
+        let mut high = Simulation::new(
+            SMALL,
+            Rules {
+                threshold: 10,
+                neighbourhood: Neighbourhood::Moore,
+            },
+        )
+        .unwrap();
+        assert!(high.step().is_empty());
+        assert_eq!(high.energy(1, 1), 10);
+
+        let low = Rules {
+            threshold: 3,
+            neighbourhood: Neighbourhood::Moore,
+        };
+        let mut simulation = Simulation::new("30\n11\n", low).unwrap();
+        assert_eq!(simulation.step(), vec![(0, 0)]);
+        assert_eq!(grid(&simulation), "02\n33\n");
+        assert_eq!(simulation.step(), vec![(1, 0), (1, 1), (0, 1), (0, 0)]);
+        assert_eq!(grid(&simulation), "00\n00\n");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Simulation::new("", Rules::default()), Err(GridError::Empty));
+        assert_eq!(
+            Simulation::new("12\n1x\n", Rules::default()),
+            Err(GridError::InvalidCharacter {
+                line: 2,
+                column: 2,
+                found: 'x'
+            })
+        );
+        assert_eq!(
+            Simulation::new("12\n123\n", Rules::default()),
+            Err(GridError::WidthMismatch {
+                line: 2,
+                expected: 2,
+                found: 3
+            })
+        );
+        assert_eq!(
+            Simulation::new(
+                "95\n11\n",
+                Rules {
+                    threshold: 3,
+                    neighbourhood: Neighbourhood::Moore,
+                }
+            ),
+            Err(GridError::AboveThreshold {
+                line: 1,
+                column: 1,
+                energy: 9,
+                threshold: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_synchronisation() {
+        let input = include_str!("../test");
+        let simulation = Simulation::new(input, Rules::default()).unwrap();
+        assert_eq!(simulation.synchronisation(1000), Synchronisation::At(195));
+        assert_eq!(simulation.synchronisation(100), Synchronisation::Unknown);
+
+        // The two cells take turns: the right one flashes on step 5 and
+        // pushes the left one to 6, which flashes on step 9 and puts the
+        // pair back where it started.
+        let pair = Simulation::new("05", Rules::default()).unwrap();
+        assert_eq!(
+            pair.synchronisation(usize::MAX),
+            Synchronisation::Never(Cycle {
+                start: 0,
+                length: 9
+            })
+        );
+        assert_eq!(
+            pair.flashes_after(1_000_000_000_000, 100),
+            Some(222_222_222_222)
+        );
+        assert_eq!(pair.flashes_after(1_000_000_000_000, 5), None);
+        assert_eq!(
+            pair.flashes_after(u64::MAX, 100),
+            Some(u64::MAX / 9 * 2 + 1)
+        );
+    }
+
+    #[test]
+    fn test_flashes_after() {
+        let input = include_str!("../test");
+        let mut simulation = Simulation::new(input, Rules::default()).unwrap();
+        assert_eq!(simulation.flashes_after(100, usize::MAX), Some(1656));
+
+        // Once synchronised on step 195 the grid flashes as one every 10
+        // steps.
+        let synchronised = simulation.flashes_after(195, usize::MAX).unwrap();
+        assert_eq!(
+            simulation.flashes_after(1_000_000_000_000, usize::MAX),
+            Some(synchronised + 99_999_999_980 * 100)
+        );
+
+        assert_eq!(simulation.flashes_after(u64::MAX, usize::MAX), None);
+
+        let expected = simulation.flashes_after(1000, usize::MAX).unwrap();
+        let total: usize = (0..1000).map(|_| simulation.step().len()).sum();
+        assert_eq!(total as u64, expected);
+    }
 }
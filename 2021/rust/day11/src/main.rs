@@ -1,5 +1,40 @@
+use day11::{Rules, Simulation, Synchronisation, STEP_LIMIT};
+use std::env;
+
 fn main() {
     let input = include_str!("../input");
     println!("{}", day11::solve1(input));
-    println!("{}", day11::solve2(input));
+
+    let mut limit = STEP_LIMIT;
+    let mut flashes_after = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .and_then(|x| x.parse().ok())
+                .expect("expected a number")
+        };
+        match arg.as_str() {
+            "--limit" => limit = value() as usize,
+            "--flashes-after" => flashes_after = Some(value()),
+            _ => panic!("unknown option `{arg}`, expected --limit or --flashes-after"),
+        }
+    }
+
+    let simulation = Simulation::new(input, Rules::default()).unwrap();
+    match simulation.synchronisation(limit) {
+        Synchronisation::At(step) => println!("{step}"),
+        Synchronisation::Never(cycle) => println!(
+            "never synchronises: cycle of length {} from step {}",
+            cycle.length, cycle.start
+        ),
+        Synchronisation::Unknown => println!("no synchronisation within {limit} steps"),
+    }
+
+    if let Some(steps) = flashes_after {
+        match simulation.flashes_after(steps, limit) {
+            Some(flashes) => println!("{flashes} flashes after {steps} steps"),
+            None => println!("no cycle within {limit} steps"),
+        }
+    }
 }
@@ -7,7 +7,7 @@ struct Pairs<'a> {
 }
 
 impl Pairs<'_> {
-    fn new(pairs: Vec<&str>, elements: Vec<u8>) -> Pairs {
+    fn new(pairs: Vec<&str>, elements: Vec<u8>) -> Pairs<'_> {
         let two_pairs: Vec<(String, String)> = pairs
             .iter()
             .zip(elements.iter())
@@ -50,7 +50,7 @@ impl Pairs<'_> {
     }
 }
 
-fn parse_values(input: &str) -> (&str, HashMap<u8, usize>, HashMap<&str, usize>, Pairs) {
+fn parse_values(input: &str) -> (&str, HashMap<u8, usize>, HashMap<&str, usize>, Pairs<'_>) {
     let mut iter = input.lines();
 
     let template = iter.by_ref().next().unwrap();